@@ -0,0 +1,67 @@
+//! A bank of identical counters, each wired to its own buses, to demonstrate
+//! [Scheduler::new_from] and building controller names at runtime.
+
+use std::sync::Arc;
+
+use shenzhen_vm::components::inputsource::{self, InputSource};
+use shenzhen_vm::components::outputsink::{self, OutputSink};
+use shenzhen_vm::controller::{intern_name, Controller, ExecError, Regs};
+use shenzhen_vm::scheduler::Scheduler;
+use shenzhen_vm::xbus::XBus;
+
+const COUNTERS: usize = 4;
+
+/// Adds each value it reads to a running total in acc, and writes out the new total.
+struct Counter {
+  name: &'static str,
+  input: XBus,
+  output: XBus,
+}
+
+impl Controller for Counter {
+  fn name(&self) -> &'static str {
+    self.name
+  }
+  fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
+    self.input.sleep()?;
+    reg.add(self.input.read()?);
+    self.output.write(reg.acc)?;
+    Ok(())
+  }
+}
+
+fn main() {
+  let mut sources: Vec<Arc<InputSource>> = vec![];
+  let mut sinks: Vec<Arc<OutputSink>> = vec![];
+
+  let mut scheduler = Scheduler::new_from(COUNTERS, |index| {
+    let (source, input) = inputsource::blocking();
+    let (sink, output) = outputsink::new(intern_name(&format!("total-{}", index)), false);
+    sources.push(source);
+    sinks.push(sink);
+
+    Box::new(Counter {
+      name: intern_name(&format!("counter-{}", index)),
+      input,
+      output,
+    })
+  });
+
+  // Counter i counts by i + 1, for three timesteps.
+  for _ in 0..3 {
+    for (index, source) in sources.iter().enumerate() {
+      source.inject(index as i32 + 1);
+    }
+    scheduler.advance().unwrap();
+  }
+
+  for (index, sink) in sinks.iter().enumerate() {
+    let mut totals = vec![];
+    sink.queue_into(&mut totals).unwrap();
+    let step = index as i32 + 1;
+    assert_eq!(totals, vec![step, 2 * step, 3 * step]);
+    println!("counter-{}: {:?}", index, totals);
+  }
+
+  scheduler.end();
+}
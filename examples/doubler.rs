@@ -5,7 +5,7 @@ use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 
 use shenzhen_vm::components::{inputsource, outputsink};
-use shenzhen_vm::controller::{Controller, Regs};
+use shenzhen_vm::controller::{Controller, ExecError, Regs};
 use shenzhen_vm::filerunner::{FileRunner, InputBus, OutputBus};
 use shenzhen_vm::rd;
 use shenzhen_vm::scheduler::Scheduler;
@@ -22,7 +22,7 @@ impl Controller for Math {
   fn name(&self) -> &'static str {
     "math"
   }
-  fn execute(&self, _reg: &mut Regs) -> Result<(), ()> {
+  fn execute(&self, _reg: &mut Regs) -> Result<(), ExecError> {
     self.input_a.sleep()?;
     let a = self.input_a.read()?;
     let b = rd!(self.input_b);
@@ -35,7 +35,9 @@ impl Controller for Math {
 
 const CSV: &[u8] = b"in input_a,in input_b,out added,out subtracted
 2,3,5,-1
-10,7,17,3
+# Values can also be written in hex or binary.
+0xA,0b111,17,3
+# No new input, so nothing is added, and the subtraction is unchanged.
 ,,,3
 3 4 5,10,13 14 15,-5
 ";
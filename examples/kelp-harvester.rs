@@ -4,7 +4,7 @@ use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 
 use shenzhen_vm::components::{inputsource, memory};
-use shenzhen_vm::controller::{Controller, Regs};
+use shenzhen_vm::controller::{Controller, ExecError, Regs};
 use shenzhen_vm::filerunner::{FileRunner, InputBus, OutputBus};
 use shenzhen_vm::gen;
 use shenzhen_vm::scheduler::{sleep, Scheduler};
@@ -22,7 +22,7 @@ impl Controller for InputConverter {
   fn name(&self) -> &'static str {
     "input-converter"
   }
-  fn execute(&self, reg: &mut Regs) -> Result<(), ()> {
+  fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
     reg.acc = self.radio_bus.read()?;
     if reg.acc != -999 {
       reg.acc *= 10;
@@ -48,7 +48,7 @@ impl Controller for Peeker {
   fn name(&self) -> &'static str {
     "peeker"
   }
-  fn execute(&self, reg: &mut Regs) -> Result<(), ()> {
+  fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
     self.from_input_converter.sleep()?;
     reg.acc = self.from_input_converter.read()?;
 
@@ -88,7 +88,7 @@ impl Controller for Splitter {
   fn name(&self) -> &'static str {
     "splitter"
   }
-  fn execute(&self, reg: &mut Regs) -> Result<(), ()> {
+  fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
     self.from_peeker.sleep()?;
 
     // dat is destination. acc is current position.
@@ -132,7 +132,7 @@ impl Controller for Searcher {
   fn name(&self) -> &'static str {
     "searcher"
   }
-  fn execute(&self, reg: &mut Regs) -> Result<(), ()> {
+  fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
     self.io.sleep()?;
     reg.acc = self.io.read()?;
     reg.dat = self.ram_read_addr.read()?;
@@ -172,24 +172,19 @@ impl Controller for MotorController {
   fn name(&self) -> &'static str {
     self.name
   }
-  fn execute(&self, reg: &mut Regs) -> Result<(), ()> {
+  fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
     self.io.sleep()?;
 
-    let input = self.io.read()?;
-    let compare = input.cmp(&reg.acc);
+    reg.tcp(self.io.read()?, reg.acc);
     self.output.store(50, Ordering::Relaxed);
 
-    // Do this with tcp
-    match compare {
-      std::cmp::Ordering::Equal => (),
-      std::cmp::Ordering::Greater => {
-        self.output.store(100, Ordering::Relaxed);
-        reg.acc += 1;
-      }
-      std::cmp::Ordering::Less => {
-        self.output.store(0, Ordering::Relaxed);
-        reg.acc -= 1;
-      }
+    if reg.if_plus() {
+      self.output.store(100, Ordering::Relaxed);
+      reg.acc += 1;
+    }
+    if reg.if_minus() {
+      self.output.store(0, Ordering::Relaxed);
+      reg.acc -= 1;
     }
 
     self.io.write(reg.acc)?;
@@ -6,7 +6,7 @@ use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 
 use shenzhen_vm::components::{expander, inputsource, memory};
-use shenzhen_vm::controller::{Controller, Regs};
+use shenzhen_vm::controller::{Controller, ExecError, Regs};
 use shenzhen_vm::filerunner::{FileRunner, InputBus, OutputBus};
 use shenzhen_vm::gen;
 use shenzhen_vm::scheduler::{sleep, Scheduler};
@@ -37,7 +37,7 @@ fn main() {
     fn name(&self) -> &'static str {
       "main"
     }
-    fn execute(&self, _: &mut Regs) -> Result<(), ()> {
+    fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
       self.keypad_bus.sleep()?;
       let value = self.keypad_bus.read()?;
       match value {
@@ -71,7 +71,7 @@ fn main() {
     fn name(&self) -> &'static str {
       "output"
     }
-    fn execute(&self, reg: &mut Regs) -> Result<(), ()> {
+    fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
       self.from_main.sleep()?;
       reg.acc = self.from_main.read()?;
       while reg.acc > 0 {
@@ -1,6 +1,21 @@
-//! Components from the game other than controllers.
+//! Components from the game other than controllers, plus a few ready-made controllers.
 
+pub mod assertsink;
+pub mod clock;
+pub mod comparator;
+pub mod counter;
+pub mod dac;
 pub mod expander;
+pub mod fifo;
 pub mod inputsource;
+pub mod latch;
+pub mod logic;
 pub mod memory;
+pub mod merge;
+pub mod mux;
 pub mod outputsink;
+pub mod pwm;
+pub mod sensor;
+pub mod shiftregister;
+pub mod simple;
+pub mod stack;
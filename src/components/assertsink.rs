@@ -0,0 +1,152 @@
+//! For checking the values written to a bus against an expected sequence while a design runs.
+
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::xbus::{TSink, XBus};
+
+/// One way the values written to an [AssertSink] differed from the expected ones. Indices count
+/// the values written, from zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssertFailure {
+  /// The value at `index` wasn't the expected one.
+  Mismatch {
+    index: usize,
+    expected: i32,
+    actual: i32,
+  },
+  /// A value was written after all the expected values.
+  Extra { index: usize, actual: i32 },
+  /// Fewer values were written than expected; the first missing one is at `index`.
+  Missing { index: usize, expected: i32 },
+}
+
+impl std::fmt::Display for AssertFailure {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AssertFailure::Mismatch {
+        index,
+        expected,
+        actual,
+      } => write!(f, "value {} was {}, expected {}", index, actual, expected),
+      AssertFailure::Extra { index, actual } => {
+        write!(f, "unexpected value {} at index {}", actual, index)
+      }
+      AssertFailure::Missing { index, expected } => {
+        write!(f, "missing value {} at index {}", expected, index)
+      }
+    }
+  }
+}
+
+/// Returned by [AssertSink::verify] when the values written didn't match.
+#[derive(Debug)]
+pub struct AssertError {
+  pub name: &'static str,
+  /// Every failure, in the order the values were written, then any missing value.
+  pub failures: Vec<AssertFailure>,
+}
+
+impl Error for AssertError {}
+
+impl std::fmt::Display for AssertError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Assertion sink '{}' failed:", self.name)?;
+    for failure in self.failures.iter() {
+      write!(f, "\n  {}", failure)?;
+    }
+    Ok(())
+  }
+}
+
+/// Accepts values written to a bus and compares each one against the next value of an expected
+/// sequence, recording any that differ. Unlike an
+/// [OutputSink](crate::components::outputsink::OutputSink), it doesn't keep the values.
+pub struct AssertSink {
+  name: &'static str,
+  expected: Vec<i32>,
+  /// The number of values written so far, and the failures among them.
+  state: Mutex<(usize, Vec<AssertFailure>)>,
+}
+
+/// Create an assertion sink expecting `expected` to be written, in order, returning it and an XBus
+/// that it's connected to. It always accepts writes, so it never blocks a writer. Call
+/// [AssertSink::verify] at the end of the run to check the values; failures aren't reported any
+/// earlier, since the writes happen on controller threads.
+///
+/// ```
+/// # use shenzhen_vm::components::assertsink::{self, AssertFailure};
+/// let (sink, bus) = assertsink::new("internal", vec![1, 2, 3]);
+/// for value in [1, 5] {
+///   bus.write(value).unwrap();
+/// }
+/// let error = sink.verify().unwrap_err();
+/// assert_eq!(
+///   error.failures,
+///   vec![
+///     AssertFailure::Mismatch { index: 1, expected: 2, actual: 5 },
+///     AssertFailure::Missing { index: 2, expected: 3 },
+///   ]
+/// );
+/// ```
+pub fn new(name: &'static str, expected: Vec<i32>) -> (Arc<AssertSink>, XBus) {
+  let xbus = XBus::new();
+  let sink = Arc::new(AssertSink {
+    name,
+    expected,
+    state: Mutex::new((0, vec![])),
+  });
+  xbus.connect_sink(Arc::clone(&sink) as Arc<AssertSink>);
+  (sink, xbus)
+}
+
+impl AssertSink {
+  /// The number of values written so far.
+  pub fn received(&self) -> usize {
+    self.state.lock().unwrap().0
+  }
+
+  /// The failures recorded so far, not counting values that are still to come.
+  pub fn failures(&self) -> Vec<AssertFailure> {
+    self.state.lock().unwrap().1.clone()
+  }
+
+  /// Check that exactly the expected values have been written, returning every failure if not.
+  pub fn verify(&self) -> Result<(), AssertError> {
+    let (received, failures) = &*self.state.lock().unwrap();
+    let mut failures = failures.clone();
+    if let Some(expected) = self.expected.get(*received) {
+      failures.push(AssertFailure::Missing {
+        index: *received,
+        expected: *expected,
+      });
+    }
+
+    if failures.is_empty() {
+      Ok(())
+    } else {
+      Err(AssertError {
+        name: self.name,
+        failures,
+      })
+    }
+  }
+}
+
+impl TSink for AssertSink {
+  fn write(&self, val: i32) {
+    let (received, failures) = &mut *self.state.lock().unwrap();
+    let index = *received;
+    *received += 1;
+
+    match self.expected.get(index) {
+      Some(expected) if *expected == val => {}
+      Some(expected) => failures.push(AssertFailure::Mismatch {
+        index,
+        expected: *expected,
+        actual: val,
+      }),
+      None => failures.push(AssertFailure::Extra { index, actual: val }),
+    }
+  }
+}
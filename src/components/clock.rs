@@ -0,0 +1,29 @@
+//! A component that reports the current timestep, like a clock chip.
+
+use std::sync::Arc;
+
+use crate::controller::MAX_VALUE;
+use crate::scheduler::current_time;
+use crate::xbus::{TSource, XBus};
+
+struct Clock;
+
+/// Creates a clock and returns the XBus it's connected to. Reading from the XBus produces the
+/// current timestep number, saturating at 999 (the largest value the game allows). The clock is
+/// always readable. The current timestep comes from the scheduler of the controller doing the
+/// read.
+pub fn new() -> XBus {
+  let xbus = XBus::new();
+  xbus.connect_source(Arc::new(Clock));
+  xbus
+}
+
+impl TSource for Clock {
+  fn can_read(&self) -> bool {
+    true
+  }
+
+  fn read(&self) -> i32 {
+    current_time().min(MAX_VALUE as u32) as i32
+  }
+}
@@ -0,0 +1,47 @@
+//! A comparator that orders values from two XBuses, implemented as a controller.
+
+use crate::controller::{Controller, ExecError, Regs};
+use crate::scheduler::sleep;
+use crate::xbus::XBus;
+
+struct Comparator {
+  name: &'static str,
+  a: XBus,
+  b: XBus,
+  out: XBus,
+}
+
+/// Creates a comparator: a controller (to pass to `Scheduler::new`) that once per timestep reads
+/// one value from `a` and one from `b`, and writes 1 to `out` if the value from `a` is greater, -1
+/// if it's less, or 0 if they're equal. `name` must be unique among the scheduler's controllers.
+///
+/// As with any controller, the reads and the write block until they can complete: the comparator
+/// waits for a value on `a`, then for a value on `b`, and then for something to consume the output.
+///
+/// ```
+/// # use shenzhen_vm::components::{comparator, inputsource, outputsink};
+/// # use shenzhen_vm::scheduler::Scheduler;
+/// let (_, a) = inputsource::from_iter(true, [5, -2, 7]);
+/// let (_, b) = inputsource::from_iter(true, [3, 4, 7]);
+/// let (sink, out) = outputsink::new("out", false);
+/// let mut scheduler = Scheduler::new(vec![comparator::new("cmp", a, b, out)]);
+/// scheduler.advance_n(3).unwrap();
+/// # scheduler.end();
+/// assert_eq!(sink.peek_all(), vec![1, -1, 0]);
+/// ```
+pub fn new(name: &'static str, a: XBus, b: XBus, out: XBus) -> Box<dyn Controller + Send> {
+  Box::new(Comparator { name, a, b, out })
+}
+
+impl Controller for Comparator {
+  fn name(&self) -> &'static str {
+    self.name
+  }
+
+  fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+    let a = self.a.read()?;
+    let b = self.b.read()?;
+    self.out.write(a.cmp(&b) as i32)?;
+    sleep(1)
+  }
+}
@@ -0,0 +1,54 @@
+//! A component that hands out increasing sequence numbers.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use crate::controller::{MAX_VALUE, MIN_VALUE};
+use crate::xbus::{TSink, TSource, XBus};
+
+struct Counter {
+  value: AtomicI32,
+  step: i32,
+}
+
+/// Creates a counter starting at `start`, and returns the XBus it's connected to. Each read from
+/// the XBus returns the current value and then adds `step` to it, saturating at the game's range of
+/// [-999, 999]. Writing to the XBus sets the current value. The counter is always readable.
+///
+/// ```
+/// # use shenzhen_vm::components::counter;
+/// let bus = counter::new(997, 2);
+/// assert_eq!((bus.read(), bus.read(), bus.read()), (Ok(997), Ok(999), Ok(999)));
+/// bus.write(10).unwrap();
+/// assert_eq!(bus.read(), Ok(10));
+/// ```
+pub fn new(start: i32, step: i32) -> XBus {
+  let xbus = XBus::new();
+  let counter = Arc::new(Counter {
+    value: AtomicI32::new(start),
+    step,
+  });
+  xbus.connect_sink(Arc::clone(&counter) as Arc<Counter>);
+  xbus.connect_source(counter);
+  xbus
+}
+
+impl TSource for Counter {
+  fn can_read(&self) -> bool {
+    true
+  }
+
+  fn read(&self) -> i32 {
+    let next = |value: i32| Some(value.saturating_add(self.step).clamp(MIN_VALUE, MAX_VALUE));
+    self
+      .value
+      .fetch_update(Ordering::Relaxed, Ordering::Relaxed, next)
+      .unwrap()
+  }
+}
+
+impl TSink for Counter {
+  fn write(&self, val: i32) {
+    self.value.store(val, Ordering::Relaxed);
+  }
+}
@@ -0,0 +1,71 @@
+//! An output component that holds the level last written to it, like a DAC or a display.
+
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use crate::xbus::{TSink, XBus};
+
+/// The number of characters in the bar drawn by the `Debug` impl.
+const BAR_WIDTH: i64 = 20;
+
+/// Holds the last value written to it, clamped to a range. Unlike an `OutputSink`, which queues
+/// every value written, this only has a current level, which stays until the next write.
+pub struct Dac {
+  name: &'static str,
+  range: RangeInclusive<i32>,
+  level: AtomicI32,
+}
+
+/// Create a new DAC, returning it and an XBus that it's connected to. Values written to the XBus
+/// are clamped into `range`. The level starts at the low end of the range. Panics if `range` is
+/// empty.
+pub fn new(name: &'static str, range: RangeInclusive<i32>) -> (Arc<Dac>, XBus) {
+  assert!(!range.is_empty(), "DAC range is empty: {:?}", range);
+
+  let xbus = XBus::new();
+  let dac = Arc::new(Dac {
+    name,
+    level: AtomicI32::new(*range.start()),
+    range,
+  });
+
+  xbus.connect_sink(Arc::clone(&dac) as Arc<Dac>);
+  (dac, xbus)
+}
+
+impl Dac {
+  /// The current level: the last value written, clamped into the range.
+  pub fn level(&self) -> i32 {
+    self.level.load(Ordering::Relaxed)
+  }
+}
+
+impl TSink for Dac {
+  fn write(&self, val: i32) {
+    let clamped = val.clamp(*self.range.start(), *self.range.end());
+    self.level.store(clamped, Ordering::Relaxed);
+  }
+}
+
+impl Debug for Dac {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let level = self.level();
+    let (low, high) = (*self.range.start() as i64, *self.range.end() as i64);
+    let filled = if high > low {
+      (level as i64 - low) * BAR_WIDTH / (high - low)
+    } else {
+      BAR_WIDTH
+    };
+
+    write!(
+      f,
+      "{} [{}{}] {}",
+      self.name,
+      "#".repeat(filled as usize),
+      " ".repeat((BAR_WIDTH - filled) as usize),
+      level
+    )
+  }
+}
@@ -1,4 +1,4 @@
-//! The component that translates between XBuses and three simple I/O pins.
+//! The component that translates between XBuses and simple I/O pins (three of them, in the game).
 
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
@@ -6,19 +6,46 @@ use std::sync::Arc;
 use crate::xbus::{TSink, TSource, XBus};
 
 struct Expander {
-  p0: Option<Arc<AtomicI32>>,
-  p1: Option<Arc<AtomicI32>>,
-  p2: Option<Arc<AtomicI32>>,
+  /// Element `i` is the pin for the `10^i` digit.
+  pins: Vec<Option<Arc<AtomicI32>>>,
+  mode: Mode,
+  /// Whether negative values are represented by negative pin levels, rather than dropping the sign.
+  signed: bool,
 }
 
+enum Mode {
+  /// Each pin is either 0 or `write_high`, and reads as 1 if it's at least `read_threshold`.
+  Digital {
+    read_threshold: i32,
+    write_high: i32,
+  },
+  /// Each pin is the digit times `scale`.
+  Analog { scale: i32 },
+}
+
+/// The most pins an expander can have, so that every combination of digits fits in an i32.
+pub const MAX_PINS: usize = 9;
+
 /// Creates an expander, the component that converts between XBus I/O and three simple I/O pins.
 ///
 /// When writing to the XBus, each simple pin is set to 100 if the corresponding digit of the XBus
 /// value is nonzero, or 0 otherwise. (p2 = hundreds digit, p1 = tens, p0 = ones.) The sign of the
-/// XBus value is ignored.
+/// XBus value is dropped, so writing -123 has the same effect as writing 123; use [new_signed] to
+/// keep it.
 ///
 /// When reading from the XBus, each digit is 1 if the corresponding simple pin's value is >= 50,
-/// or 0 otherwise.
+/// or 0 otherwise, so the value read is never negative.
+///
+/// ```
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// # use shenzhen_vm::components::expander;
+/// let pins = [0, 1, 2].map(|_| Arc::new(AtomicI32::new(0)));
+/// let bus = expander::new(Some(pins[0].clone()), Some(pins[1].clone()), Some(pins[2].clone()));
+/// bus.write(-123).unwrap();
+/// assert_eq!(pins.each_ref().map(|p| p.load(Ordering::Relaxed)), [100, 100, 100]);
+/// assert_eq!(bus.read(), Ok(111));
+/// ```
 ///
 /// This just returns a single XBus, even though the in-game component has three XBus pins. They
 /// all do exactly the same thing, so the effect is the same as if there were just a single XBus
@@ -28,8 +55,140 @@ pub fn new(
   p1: Option<Arc<AtomicI32>>,
   p2: Option<Arc<AtomicI32>>,
 ) -> XBus {
+  with_threshold(p0, p1, p2, 50, 100)
+}
+
+/// Creates an expander like [new], but which keeps the sign of negative values: writing a negative
+/// value sets the pins of nonzero digits to -100 instead of 100. When reading, a pin with a value
+/// <= -50 counts as a digit of -1, so pins written this way read back as the same negative value.
+///
+/// ```
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// # use shenzhen_vm::components::expander;
+/// let pins = [0, 1, 2].map(|_| Arc::new(AtomicI32::new(0)));
+/// let bus =
+///   expander::new_signed(Some(pins[0].clone()), Some(pins[1].clone()), Some(pins[2].clone()));
+/// bus.write(-120).unwrap();
+/// assert_eq!(pins.each_ref().map(|p| p.load(Ordering::Relaxed)), [0, -100, -100]);
+/// assert_eq!(bus.read(), Ok(-110));
+/// ```
+pub fn new_signed(
+  p0: Option<Arc<AtomicI32>>,
+  p1: Option<Arc<AtomicI32>>,
+  p2: Option<Arc<AtomicI32>>,
+) -> XBus {
+  make(
+    vec![p0, p1, p2],
+    Mode::Digital {
+      read_threshold: 50,
+      write_high: 100,
+    },
+    true,
+  )
+}
+
+/// Creates an expander like [new], but with custom levels: when reading from the XBus, a digit is
+/// 1 if the corresponding pin's value is >= `read_threshold`; when writing to the XBus, pins for
+/// nonzero digits are set to `write_high`.
+///
+/// ```
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// # use shenzhen_vm::components::expander;
+/// let p0 = Arc::new(AtomicI32::new(30));
+/// let bus = expander::with_threshold(Some(p0.clone()), None, None, 30, 70);
+/// assert_eq!(bus.read(), Ok(1));
+///
+/// p0.store(29, Ordering::Relaxed);
+/// assert_eq!(bus.read(), Ok(0));
+///
+/// bus.write(1).unwrap();
+/// assert_eq!(p0.load(Ordering::Relaxed), 70);
+/// ```
+pub fn with_threshold(
+  p0: Option<Arc<AtomicI32>>,
+  p1: Option<Arc<AtomicI32>>,
+  p2: Option<Arc<AtomicI32>>,
+  read_threshold: i32,
+  write_high: i32,
+) -> XBus {
+  make(
+    vec![p0, p1, p2],
+    Mode::Digital {
+      read_threshold,
+      write_high,
+    },
+    false,
+  )
+}
+
+/// Creates an expander that keeps the magnitude of each digit, rather than just whether it's
+/// nonzero. Equivalent to [analog_with_scale] with a scale of 10, so digits 0-9 map to pin levels
+/// 0-90.
+pub fn analog(
+  p0: Option<Arc<AtomicI32>>,
+  p1: Option<Arc<AtomicI32>>,
+  p2: Option<Arc<AtomicI32>>,
+) -> XBus {
+  analog_with_scale(p0, p1, p2, 10)
+}
+
+/// Creates an analog expander: when writing to the XBus, each pin is set to the corresponding digit
+/// of the value times `scale`. The sign of the XBus value is ignored.
+///
+/// When reading from the XBus, each digit is the corresponding pin's value divided by `scale`,
+/// rounded to the nearest integer (halves round up) and clamped to 0-9.
+///
+/// ```
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// # use shenzhen_vm::components::expander;
+/// let p0 = Arc::new(AtomicI32::new(0));
+/// let p1 = Arc::new(AtomicI32::new(0));
+/// let bus = expander::analog(Some(p0.clone()), Some(p1.clone()), None);
+/// bus.write(-47).unwrap();
+/// assert_eq!((p0.load(Ordering::Relaxed), p1.load(Ordering::Relaxed)), (70, 40));
+///
+/// p0.store(14, Ordering::Relaxed);
+/// p1.store(15, Ordering::Relaxed);
+/// assert_eq!(bus.read(), Ok(21));
+/// ```
+///
+/// Panics if `scale` isn't positive.
+pub fn analog_with_scale(
+  p0: Option<Arc<AtomicI32>>,
+  p1: Option<Arc<AtomicI32>>,
+  p2: Option<Arc<AtomicI32>>,
+  scale: i32,
+) -> XBus {
+  assert!(scale > 0, "Analog expander scale must be positive");
+  make(vec![p0, p1, p2], Mode::Analog { scale }, false)
+}
+
+/// Creates an expander like [new], but with any number of pins from 1 to [MAX_PINS]. Element `i`
+/// of `pins` corresponds to the `10^i` digit of the XBus value.
+pub fn new_n(pins: Vec<Option<Arc<AtomicI32>>>) -> XBus {
+  make(
+    pins,
+    Mode::Digital {
+      read_threshold: 50,
+      write_high: 100,
+    },
+    false,
+  )
+}
+
+fn make(pins: Vec<Option<Arc<AtomicI32>>>, mode: Mode, signed: bool) -> XBus {
+  assert!(
+    (1..=MAX_PINS).contains(&pins.len()),
+    "Expanders must have between 1 and {} pins, got {}",
+    MAX_PINS,
+    pins.len()
+  );
+
   let xbus = XBus::new();
-  let expander = Arc::new(Expander { p0, p1, p2 });
+  let expander = Arc::new(Expander { pins, mode, signed });
   xbus.connect_sink(Arc::clone(&expander) as Arc<Expander>);
   xbus.connect_source(expander);
 
@@ -42,27 +201,64 @@ impl TSource for Expander {
   }
 
   fn read(&self) -> i32 {
-    let to_bit = |atom: &Arc<AtomicI32>| (atom.load(Ordering::Relaxed) >= 50) as i32;
-    let mut total = 0;
-
-    total += 100 * self.p2.as_ref().map_or(0, to_bit);
-    total += 10 * self.p1.as_ref().map_or(0, to_bit);
-    total += self.p0.as_ref().map_or(0, to_bit);
+    let magnitude_digit = |level: i32| match self.mode {
+      Mode::Digital { read_threshold, .. } => (level >= read_threshold) as i32,
+      Mode::Analog { scale } => ((level.max(0) + scale / 2) / scale).min(9),
+    };
+    let to_digit = |atom: &Arc<AtomicI32>| {
+      let level = atom.load(Ordering::Relaxed);
+      if self.signed && level < 0 {
+        -magnitude_digit(level.saturating_neg())
+      } else {
+        magnitude_digit(level)
+      }
+    };
 
-    total
+    // With at most MAX_PINS digits of magnitude at most 9, this sum can't overflow.
+    self
+      .pins
+      .iter()
+      .zip(place_values())
+      .map(|(pin, place)| place * pin.as_ref().map_or(0, to_digit))
+      .sum()
   }
 }
+
 impl TSink for Expander {
   fn write(&self, val: i32) {
     let abs_val = val.abs();
-    if let Some(atom) = &self.p2 {
-      atom.store(if abs_val >= 100 { 100 } else { 0 }, Ordering::Relaxed);
-    }
-    if let Some(atom) = &self.p1 {
-      atom.store(if abs_val % 100 >= 10 { 100 } else { 0 }, Ordering::Relaxed);
-    }
-    if let Some(atom) = &self.p0 {
-      atom.store(if abs_val % 10 >= 1 { 100 } else { 0 }, Ordering::Relaxed);
+    let last = self.pins.len() - 1;
+
+    for (i, (pin, place)) in self.pins.iter().zip(place_values()).enumerate() {
+      if let Some(atom) = pin {
+        let level = match self.mode {
+          Mode::Digital { write_high, .. } => {
+            // The top pin is high for any value that has digits at or above its place.
+            let high = if i == last {
+              abs_val >= place
+            } else {
+              (abs_val / place) % 10 != 0
+            };
+            if high {
+              write_high
+            } else {
+              0
+            }
+          }
+          Mode::Analog { scale } => ((abs_val / place) % 10) * scale,
+        };
+        let level = if self.signed && val < 0 {
+          -level
+        } else {
+          level
+        };
+        atom.store(level, Ordering::Relaxed);
+      }
     }
   }
 }
+
+/// 1, 10, 100, ... for as many pins as there are.
+fn place_values() -> impl Iterator<Item = i32> {
+  (0..MAX_PINS as u32).map(|i| 10i32.pow(i))
+}
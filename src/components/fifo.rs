@@ -0,0 +1,127 @@
+//! A FIFO queue component, read and written over a single XBus.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use crate::xbus::{TSink, TSource, XBus};
+
+struct FifoInner {
+  contents: Mutex<VecDeque<i32>>,
+  depth: usize,
+}
+
+/// Represents a queue of values with a fixed depth, for buffering between a bursty producer and a
+/// steady consumer.
+///
+/// Writing to `data` adds a value at the back of the queue, and reading from it takes the value at
+/// the front, so values come out in the order they went in. Values stay queued across timesteps.
+/// Reading from an empty queue blocks until something is written, and writing to a full queue
+/// blocks until something is read, at which point the blocked value joins the back of the queue.
+/// As with other blocking XBuses, use `XBus::sleep` to wait for the queue to be nonempty before
+/// reading.
+///
+/// ```
+/// # use shenzhen_vm::components::{fifo, outputsink};
+/// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+/// # use shenzhen_vm::scheduler::{sleep, Scheduler};
+/// # use shenzhen_vm::xbus::XBus;
+/// // Writes 1, 2, 3, ..., one per timestep.
+/// struct Producer(XBus);
+/// impl Controller for Producer {
+///   fn name(&self) -> &'static str { "producer" }
+///   fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
+///     reg.acc += 1;
+///     self.0.write(reg.acc)?;
+///     sleep(1)
+///   }
+/// }
+///
+/// // Waits for the queue to fill, then forwards one value per timestep. It runs after the producer
+/// // in each timestep, since controllers are woken in name order.
+/// struct Consumer(XBus, XBus);
+/// impl Controller for Consumer {
+///   fn name(&self) -> &'static str { "reader" }
+///   fn init(&self, _: &mut Regs) -> Result<(), ExecError> { sleep(3) }
+///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+///     self.1.write(self.0.read()?)?;
+///     sleep(1)
+///   }
+/// }
+///
+/// let fifo = fifo::new(3);
+/// let (sink, out) = outputsink::new("out", false);
+/// let mut scheduler = Scheduler::new(vec![
+///   Box::new(Producer(fifo.data.clone())),
+///   Box::new(Consumer(fifo.data.clone(), out)),
+/// ]);
+/// scheduler.advance_n(3).unwrap();
+/// assert_eq!(format!("{:?}", fifo), "Fifo { occupancy: 3/3, contents: [1, 2, 3] }");
+///
+/// // From now on, the producer blocks on a full queue until the consumer reads.
+/// scheduler.advance_n(3).unwrap();
+/// assert_eq!(sink.peek_all(), vec![1, 2, 3]);
+/// assert_eq!(format!("{:?}", fifo), "Fifo { occupancy: 3/3, contents: [4, 5, 6] }");
+/// # scheduler.end();
+/// ```
+pub struct Fifo {
+  pub data: XBus,
+  inner: Arc<FifoInner>,
+}
+
+/// Create an empty queue that holds up to `depth` values.
+pub fn new(depth: usize) -> Fifo {
+  let data = XBus::new();
+  let inner = Arc::new(FifoInner {
+    contents: Mutex::new(VecDeque::with_capacity(depth)),
+    depth,
+  });
+
+  data.connect_source(Arc::clone(&inner) as Arc<FifoInner>);
+  data.connect_sink(Arc::clone(&inner) as Arc<FifoInner>);
+
+  Fifo { data, inner }
+}
+
+impl TSource for FifoInner {
+  fn can_read(&self) -> bool {
+    !self.contents.lock().unwrap().is_empty()
+  }
+
+  fn read(&self) -> i32 {
+    self
+      .contents
+      .lock()
+      .unwrap()
+      .pop_front()
+      .expect("Cannot read from empty FIFO")
+  }
+
+  fn holds_writes(&self) -> bool {
+    true
+  }
+}
+
+impl TSink for FifoInner {
+  fn can_write(&self) -> bool {
+    self.contents.lock().unwrap().len() < self.depth
+  }
+
+  fn write(&self, val: i32) {
+    self.contents.lock().unwrap().push_back(val);
+  }
+}
+
+impl Debug for Fifo {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // Front of the queue first.
+    let contents = self.inner.contents.lock().unwrap();
+    f.debug_struct("Fifo")
+      .field(
+        "occupancy",
+        &format_args!("{}/{}", contents.len(), self.inner.depth),
+      )
+      .field("contents", &*contents)
+      .finish()
+  }
+}
@@ -1,26 +1,35 @@
 //! For putting program input onto an XBus.
 
 use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::scheduler::current_time;
 use crate::xbus::{TSource, XBus};
 
 enum InputSourceType {
   Blocking,
   NonBlocking,
+  Looping,
+  BlockingWithDefault { default: i32, patience: u32 },
 }
 
 /// Puts program input onto an XBus. Internally maintains a queue of values, and can be created as
-/// either blocking or nonblocking.
+/// blocking, nonblocking, or looping.
 pub struct InputSource {
   source_type: InputSourceType,
   queue: Mutex<VecDeque<i32>>,
+  /// The timestep in which the queue last became empty.
+  empty_since: AtomicU32,
 }
 
 fn make(source_type: InputSourceType) -> (Arc<InputSource>, XBus) {
   let source = Arc::new(InputSource {
     source_type,
     queue: Mutex::new(VecDeque::new()),
+    empty_since: AtomicU32::new(0),
   });
   let bus = XBus::new();
   bus.connect_source(Arc::clone(&source) as Arc<InputSource>);
@@ -41,19 +50,158 @@ pub fn nonblocking() -> (Arc<InputSource>, XBus) {
   make(InputSourceType::NonBlocking)
 }
 
+/// Creates a source that blocks like [blocking] while its queue is empty, but only for `patience`
+/// timesteps after the queue became empty. After that, until another value is injected, it's always
+/// readable and produces `default`, like a sensor reporting a fallback reading when its real input
+/// is late. A `patience` of `u32::MAX` effectively waits forever. Returns the same things as
+/// [blocking].
+///
+/// The timesteps are counted from when the queue became empty, or from zero if it was empty from
+/// the start or when the scheduler was last reset.
+///
+/// A controller blocked in `XBus::read` isn't woken when the source becomes readable, and counts
+/// towards a deadlock at the end of the timestep, so wait for this source with `XBus::sleep` or
+/// `XBus::read_timeout` instead.
+///
+/// ```
+/// # use shenzhen_vm::components::inputsource;
+/// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+/// # use shenzhen_vm::scheduler::{sleep, Scheduler};
+/// # use shenzhen_vm::xbus::XBus;
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// struct Reader(XBus, Arc<AtomicI32>);
+/// impl Controller for Reader {
+///   fn name(&self) -> &'static str { "reader" }
+///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+///     self.0.sleep()?;
+///     self.1.store(self.0.read()?, Ordering::Relaxed);
+///     sleep(1)
+///   }
+/// }
+///
+/// let (source, bus) = inputsource::blocking_with_default(-1, 3);
+/// let out = Arc::new(AtomicI32::new(0));
+/// let mut scheduler = Scheduler::new(vec![Box::new(Reader(bus, out.clone()))]);
+/// source.inject(5);
+/// scheduler.advance_n(2).unwrap();
+/// assert_eq!(out.load(Ordering::Relaxed), 5);
+/// // The queue became empty in timestep 1, so the default is produced from timestep 4 on.
+/// scheduler.advance_n(2).unwrap();
+/// assert_eq!((scheduler.time(), out.load(Ordering::Relaxed)), (4, -1));
+///
+/// // After a reset, the count starts again from timestep 0, not from when the queue last emptied.
+/// source.inject(6);
+/// scheduler.advance_n(2).unwrap();
+/// scheduler.reset();
+/// out.store(0, Ordering::Relaxed);
+/// scheduler.advance_n(3).unwrap();
+/// assert_eq!(out.load(Ordering::Relaxed), -1);
+/// # scheduler.end();
+/// ```
+pub fn blocking_with_default(default: i32, patience: u32) -> (Arc<InputSource>, XBus) {
+  make(InputSourceType::BlockingWithDefault { default, patience })
+}
+
+/// Creates a looping source, which cycles through the given values endlessly. Each read takes the
+/// value at the front of the queue and puts it back at the end, so values added with `inject` join
+/// the cycle. If the queue is emptied with [InputSource::clear], reads block, as with a blocking
+/// source, until values are injected again. Panics if `values` is empty.
+pub fn looping(values: Vec<i32>) -> (Arc<InputSource>, XBus) {
+  assert!(
+    !values.is_empty(),
+    "Looping source needs at least one value"
+  );
+  let (source, bus) = make(InputSourceType::Looping);
+  source.inject_all(values);
+  (source, bus)
+}
+
+/// Creates a source that's blocking or nonblocking according to `blocking`, with the given values
+/// already in its queue. Returns the same things as [blocking] and [nonblocking].
+pub fn from_iter(
+  blocking: bool,
+  values: impl IntoIterator<Item = i32>,
+) -> (Arc<InputSource>, XBus) {
+  let (source, bus) = make(if blocking {
+    InputSourceType::Blocking
+  } else {
+    InputSourceType::NonBlocking
+  });
+  source.inject_all(values);
+  (source, bus)
+}
+
 impl InputSource {
   /// Add a value to the queue. Unlike controllers' XBus writes, it's not an error for these values
   /// to stay in the queue across timesteps.
   pub fn inject(&self, value: i32) {
     self.queue.lock().unwrap().push_back(value);
   }
+
+  /// Add all the given values to the queue, in order.
+  pub fn inject_all(&self, values: impl IntoIterator<Item = i32>) {
+    self.queue.lock().unwrap().extend(values);
+  }
+
+  /// The number of values in the queue. For a nonblocking source, this doesn't count the -999s it
+  /// produces once the queue is empty.
+  pub fn len(&self) -> usize {
+    self.queue.lock().unwrap().len()
+  }
+
+  /// Whether the queue is empty, e.g. because controllers have consumed all the injected values.
+  pub fn is_empty(&self) -> bool {
+    self.queue.lock().unwrap().is_empty()
+  }
+
+  /// Remove all values from the queue.
+  pub fn clear(&self) {
+    self.queue.lock().unwrap().clear();
+    self.empty_since.store(current_time(), Ordering::Relaxed);
+  }
+
+  /// Connect this source to another XBus, in addition to the one it was created with, e.g. to
+  /// combine it with other sources on a bus that already exists. Reads from either bus take values
+  /// from the same queue.
+  ///
+  /// When a bus has several sources, a read takes a value written by a controller if there is one,
+  /// and otherwise from the first readable source in the order they were connected. So a
+  /// nonblocking or looping source hides any sources connected after it.
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::{inputsource, sensor};
+  /// let bus = sensor::new(vec![(0, 7)]);
+  /// let (source, _) = inputsource::from_iter(true, [1, 2]);
+  /// source.attach(&bus);
+  /// assert_eq!((bus.read(), bus.read()), (Ok(7), Ok(7)));
+  ///
+  /// let (first, bus) = inputsource::from_iter(true, [1]);
+  /// let (second, _) = inputsource::from_iter(true, [2]);
+  /// second.attach(&bus);
+  /// assert_eq!((bus.read(), bus.read()), (Ok(1), Ok(2)));
+  /// # assert!(first.is_empty());
+  /// ```
+  pub fn attach(self: &Arc<Self>, bus: &XBus) {
+    bus.connect_source(Arc::clone(self) as Arc<InputSource>);
+  }
 }
 
 impl TSource for InputSource {
   fn can_read(&self) -> bool {
     match &self.source_type {
-      InputSourceType::Blocking => !self.queue.lock().unwrap().is_empty(),
+      InputSourceType::Blocking | InputSourceType::Looping => {
+        !self.queue.lock().unwrap().is_empty()
+      }
       InputSourceType::NonBlocking => true,
+      InputSourceType::BlockingWithDefault { patience, .. } => {
+        !self.queue.lock().unwrap().is_empty()
+          || current_time()
+            >= self
+              .empty_since
+              .load(Ordering::Relaxed)
+              .saturating_add(*patience)
+      }
     }
   }
 
@@ -62,6 +210,133 @@ impl TSource for InputSource {
     match &self.source_type {
       InputSourceType::Blocking => queue.pop_front().expect("Cannot read from empty queue"),
       InputSourceType::NonBlocking => queue.pop_front().unwrap_or(-999),
+      InputSourceType::Looping => {
+        let value = queue.pop_front().expect("Looping source has no values");
+        queue.push_back(value);
+        value
+      }
+      InputSourceType::BlockingWithDefault { default, .. } => match queue.pop_front() {
+        Some(value) => {
+          if queue.is_empty() {
+            self.empty_since.store(current_time(), Ordering::Relaxed);
+          }
+          value
+        }
+        None => *default,
+      },
+    }
+  }
+
+  fn reset(&self) {
+    self.empty_since.store(0, Ordering::Relaxed);
+  }
+}
+
+type SampleFn = Box<dyn Fn(u32) -> i32 + Send>;
+
+struct Generator {
+  /// The function producing samples, and the number of samples produced so far.
+  state: Mutex<(SampleFn, u32)>,
+}
+
+/// Creates a source that computes its values instead of taking them from a queue. The `n`th read
+/// (counting from zero) produces `f(n)`. Like a nonblocking source, it's always readable.
+pub fn generator(f: Box<dyn Fn(u32) -> i32 + Send>) -> XBus {
+  let bus = XBus::new();
+  bus.connect_source(Arc::new(Generator {
+    state: Mutex::new((f, 0)),
+  }));
+  bus
+}
+
+/// Creates a generator producing `start`, `start + step`, `start + 2 * step`, etc.
+pub fn ramp(start: i32, step: i32) -> XBus {
+  generator(Box::new(move |n| {
+    start.wrapping_add(step.wrapping_mul(n as i32))
+  }))
+}
+
+/// Creates a generator producing a square wave that's `high` for the first half of each `period`
+/// reads, and `low` for the rest. Panics if `period` is zero.
+pub fn square(low: i32, high: i32, period: u32) -> XBus {
+  assert!(period > 0, "Square wave period must be nonzero");
+  generator(Box::new(move |n| {
+    if n % period < period.div_ceil(2) {
+      high
+    } else {
+      low
     }
+  }))
+}
+
+/// Creates a generator producing a sine wave centered on zero, with the given amplitude, repeating
+/// every `period` reads. Values are rounded to the nearest integer. Panics if `period` is zero.
+pub fn sine(amplitude: i32, period: u32) -> XBus {
+  assert!(period > 0, "Sine wave period must be nonzero");
+  generator(Box::new(move |n| {
+    let phase = (n % period) as f64 / period as f64;
+    (amplitude as f64 * (phase * std::f64::consts::TAU).sin()).round() as i32
+  }))
+}
+
+impl TSource for Generator {
+  fn can_read(&self) -> bool {
+    true
   }
+
+  fn read(&self) -> i32 {
+    let mut state = self.state.lock().unwrap();
+    let value = (state.0)(state.1);
+    state.1 = state.1.wrapping_add(1);
+    value
+  }
+}
+
+/// Creates a generator producing uniformly random values in `range`. The sequence of values is
+/// determined entirely by `seed`, so a run can be reproduced by using the same seed again.
+/// Different seeds give unrelated sequences, so sweeping over seeds tests different inputs.
+/// Panics if `range` is empty.
+///
+/// ```
+/// # use shenzhen_vm::components::inputsource;
+/// let draw = |seed, count| -> Vec<i32> {
+///   let bus = inputsource::random(seed, 0..=999);
+///   (0..count).map(|_| bus.read().unwrap()).collect()
+/// };
+/// assert_eq!(draw(7, 8), draw(7, 8));
+/// // Seed 8 isn't seed 7 shifted by one read.
+/// assert_ne!(draw(8, 7), draw(7, 8)[1..]);
+/// ```
+pub fn random(seed: u64, range: RangeInclusive<i32>) -> XBus {
+  let (start, end) = range.into_inner();
+  assert!(start <= end, "Random source range is empty");
+  let span = (end as i64 - start as i64 + 1) as u64;
+
+  generator(Box::new(move |n| {
+    let sample = seeded_draw(seed, n as u64);
+    (start as i64 + (sample % span) as i64) as i32
+  }))
+}
+
+/// Like [random], but picks a seed from the system clock. Returns the seed, so that a failing run
+/// can be recorded and reproduced with [random], along with the XBus.
+pub fn random_unseeded(range: RangeInclusive<i32>) -> (u64, XBus) {
+  let seed = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |d| d.as_nanos() as u64);
+  (seed, random(seed, range))
+}
+
+/// The `n`th pseudorandom draw for `seed`. The seed is mixed before the counter is added, so that
+/// the draws for nearby seeds aren't shifted copies of each other.
+pub(crate) fn seeded_draw(seed: u64, n: u64) -> u64 {
+  splitmix64(splitmix64(seed) ^ n)
+}
+
+/// The SplitMix64 mixing function: a cheap way to turn a counter into well-distributed bits.
+pub(crate) fn splitmix64(x: u64) -> u64 {
+  let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  z ^ (z >> 31)
 }
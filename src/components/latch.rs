@@ -0,0 +1,47 @@
+//! A one-cell register that holds the last value written to it, like a sample-and-hold.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use crate::xbus::{TSink, TSource, XBus};
+
+struct Latch {
+  value: AtomicI32,
+}
+
+/// Creates a latch holding `initial`, and returns the XBus it's connected to. Writing to the XBus
+/// replaces the stored value; reading from it returns the stored value without consuming it, so
+/// the latch is always readable and any number of reads see the same value until the next write.
+///
+/// ```
+/// # use shenzhen_vm::components::latch;
+/// let bus = latch::new(5);
+/// assert_eq!(bus.read(), Ok(5));
+/// bus.write(8).unwrap();
+/// assert_eq!((bus.read(), bus.read()), (Ok(8), Ok(8)));
+/// ```
+pub fn new(initial: i32) -> XBus {
+  let xbus = XBus::new();
+  let latch = Arc::new(Latch {
+    value: AtomicI32::new(initial),
+  });
+  xbus.connect_sink(Arc::clone(&latch) as Arc<Latch>);
+  xbus.connect_source(latch);
+  xbus
+}
+
+impl TSource for Latch {
+  fn can_read(&self) -> bool {
+    true
+  }
+
+  fn read(&self) -> i32 {
+    self.value.load(Ordering::Relaxed)
+  }
+}
+
+impl TSink for Latch {
+  fn write(&self, val: i32) {
+    self.value.store(val, Ordering::Relaxed);
+  }
+}
@@ -0,0 +1,166 @@
+//! Logic gates on simple I/O pins, implemented as controllers.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use crate::controller::{Controller, ExecError, Regs};
+use crate::scheduler::sleep;
+
+struct Gate {
+  name: &'static str,
+  inputs: Vec<Arc<AtomicI32>>,
+  op: fn(&[bool]) -> bool,
+  out: Arc<AtomicI32>,
+}
+
+impl Controller for Gate {
+  fn name(&self) -> &'static str {
+    self.name
+  }
+
+  fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+    let bits: Vec<bool> = self
+      .inputs
+      .iter()
+      .map(|pin| pin.load(Ordering::Relaxed) >= 50)
+      .collect();
+    let level = if (self.op)(&bits) { 100 } else { 0 };
+    self.out.store(level, Ordering::Relaxed);
+    sleep(1)
+  }
+}
+
+fn gate(
+  name: &'static str,
+  inputs: Vec<Arc<AtomicI32>>,
+  op: fn(&[bool]) -> bool,
+  out: Arc<AtomicI32>,
+) -> Box<dyn Controller + Send> {
+  Box::new(Gate {
+    name,
+    inputs,
+    op,
+    out,
+  })
+}
+
+/// Creates an AND gate. Like the other gates, it's a controller (to pass to `Scheduler::new`) that
+/// once per timestep treats each input pin as high if it's >= 50, and sets `out` to 100 if the
+/// result is high, or 0 otherwise. `name` must be unique among the scheduler's controllers.
+///
+/// Inputs from 1 to 49 count as low, even though they're nonzero.
+///
+/// ```
+/// # use shenzhen_vm::components::logic;
+/// # use shenzhen_vm::scheduler::Scheduler;
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// let pin = || Arc::new(AtomicI32::new(0));
+/// let (a, b, out) = (pin(), pin(), pin());
+/// let mut scheduler = Scheduler::new(vec![logic::and("and", a.clone(), b.clone(), out.clone())]);
+/// let table = [
+///   (0, 0, 0),
+///   (0, 100, 0),
+///   (100, 0, 0),
+///   (100, 100, 100),
+///   (1, 100, 0),
+///   (49, 50, 0),
+///   (50, 50, 100),
+/// ];
+/// for (a_in, b_in, expected) in table {
+///   a.store(a_in, Ordering::Relaxed);
+///   b.store(b_in, Ordering::Relaxed);
+///   scheduler.advance().unwrap();
+///   assert_eq!(out.load(Ordering::Relaxed), expected);
+/// }
+/// # scheduler.end();
+/// ```
+pub fn and(
+  name: &'static str,
+  a: Arc<AtomicI32>,
+  b: Arc<AtomicI32>,
+  out: Arc<AtomicI32>,
+) -> Box<dyn Controller + Send> {
+  gate(name, vec![a, b], |bits| bits[0] && bits[1], out)
+}
+
+/// Creates an OR gate. See [and].
+///
+/// ```
+/// # use shenzhen_vm::components::logic;
+/// # use shenzhen_vm::scheduler::Scheduler;
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// let pin = || Arc::new(AtomicI32::new(0));
+/// let (a, b, out) = (pin(), pin(), pin());
+/// let mut scheduler = Scheduler::new(vec![logic::or("or", a.clone(), b.clone(), out.clone())]);
+/// let table = [(0, 0, 0), (0, 100, 100), (100, 0, 100), (100, 100, 100)];
+/// for (a_in, b_in, expected) in table {
+///   a.store(a_in, Ordering::Relaxed);
+///   b.store(b_in, Ordering::Relaxed);
+///   scheduler.advance().unwrap();
+///   assert_eq!(out.load(Ordering::Relaxed), expected);
+/// }
+/// # scheduler.end();
+/// ```
+pub fn or(
+  name: &'static str,
+  a: Arc<AtomicI32>,
+  b: Arc<AtomicI32>,
+  out: Arc<AtomicI32>,
+) -> Box<dyn Controller + Send> {
+  gate(name, vec![a, b], |bits| bits[0] || bits[1], out)
+}
+
+/// Creates an XOR gate. See [and].
+///
+/// ```
+/// # use shenzhen_vm::components::logic;
+/// # use shenzhen_vm::scheduler::Scheduler;
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// let pin = || Arc::new(AtomicI32::new(0));
+/// let (a, b, out) = (pin(), pin(), pin());
+/// let mut scheduler = Scheduler::new(vec![logic::xor("xor", a.clone(), b.clone(), out.clone())]);
+/// let table = [(0, 0, 0), (0, 100, 100), (100, 0, 100), (100, 100, 0)];
+/// for (a_in, b_in, expected) in table {
+///   a.store(a_in, Ordering::Relaxed);
+///   b.store(b_in, Ordering::Relaxed);
+///   scheduler.advance().unwrap();
+///   assert_eq!(out.load(Ordering::Relaxed), expected);
+/// }
+/// # scheduler.end();
+/// ```
+pub fn xor(
+  name: &'static str,
+  a: Arc<AtomicI32>,
+  b: Arc<AtomicI32>,
+  out: Arc<AtomicI32>,
+) -> Box<dyn Controller + Send> {
+  gate(name, vec![a, b], |bits| bits[0] != bits[1], out)
+}
+
+/// Creates a NOT gate. See [and].
+///
+/// ```
+/// # use shenzhen_vm::components::logic;
+/// # use shenzhen_vm::scheduler::Scheduler;
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// let pin = || Arc::new(AtomicI32::new(0));
+/// let (a, out) = (pin(), pin());
+/// let mut scheduler = Scheduler::new(vec![logic::not("not", a.clone(), out.clone())]);
+/// for (a_in, expected) in [(0, 100), (100, 0)] {
+///   a.store(a_in, Ordering::Relaxed);
+///   scheduler.advance().unwrap();
+///   assert_eq!(out.load(Ordering::Relaxed), expected);
+/// }
+/// # scheduler.end();
+/// ```
+pub fn not(
+  name: &'static str,
+  a: Arc<AtomicI32>,
+  out: Arc<AtomicI32>,
+) -> Box<dyn Controller + Send> {
+  gate(name, vec![a], |bits| !bits[0], out)
+}
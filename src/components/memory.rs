@@ -1,8 +1,10 @@
-//! RAM and ROM components from the game (14 cells, two independent pointers).
+//! RAM and ROM components from the game (14 cells, two independent pointers), plus variants of
+//! other sizes.
 
 use std::fmt::{Debug, Write};
 use std::sync::{Arc, Mutex};
 
+use crate::controller::{current_name, raise_fault};
 use crate::xbus::{TSink, TSource, XBus};
 
 struct AddrPin {
@@ -13,16 +15,18 @@ struct AddrPin {
 struct DataPin {
   mem: Arc<Mutex<MemInner>>,
   index: usize,
+  writable: bool,
 }
 
 struct MemInner {
-  contents: [i32; 14],
+  contents: Vec<i32>,
   pointers: [usize; 2],
+  /// Whether out-of-range addresses are faults instead of wrapping.
+  strict: bool,
 }
 
-fn adjust_index(index: i32) -> usize {
-  let modded = index % 14;
-  (if modded < 0 { modded + 14 } else { modded }) as usize
+fn adjust_index(index: i32, len: usize) -> usize {
+  index.rem_euclid(len as i32) as usize
 }
 
 impl TSource for DataPin {
@@ -35,7 +39,7 @@ impl TSource for DataPin {
     let current_index = mem.pointers[self.index];
 
     let result = mem.contents[current_index];
-    let new_index = adjust_index(current_index as i32 + 1);
+    let new_index = adjust_index(current_index as i32 + 1, mem.contents.len());
     mem.pointers[self.index] = new_index;
     result
   }
@@ -43,11 +47,21 @@ impl TSource for DataPin {
 
 impl TSink for DataPin {
   fn write(&self, val: i32) {
+    if !self.writable {
+      raise_fault(format!(
+        "Write of {} to ROM data pin {} from controller {}",
+        val,
+        self.index,
+        current_name()
+      ));
+      return;
+    }
+
     let mut mem = self.mem.lock().unwrap();
     let current_index = mem.pointers[self.index];
 
     mem.contents[current_index] = val;
-    mem.pointers[self.index] = adjust_index(current_index as i32 + 1);
+    mem.pointers[self.index] = adjust_index(current_index as i32 + 1, mem.contents.len());
   }
 }
 
@@ -63,23 +77,110 @@ impl TSource for AddrPin {
 
 impl TSink for AddrPin {
   fn write(&self, val: i32) {
-    self.mem.lock().unwrap().pointers[self.index] = adjust_index(val);
+    let mut mem = self.mem.lock().unwrap();
+    let len = mem.contents.len();
+    if mem.strict && !(0..len as i32).contains(&val) {
+      raise_fault(format!(
+        "Address {} out of range for {} cells on address pin {}",
+        val, len, self.index
+      ));
+      return;
+    }
+    mem.pointers[self.index] = adjust_index(val, len);
   }
 }
 
 /// Represents a RAM or ROM module.
 ///
-/// Internally, there's an array of 14 ints for the contents, and two indexes into that array.
-/// `addr0` and `addr1` read and write those two indexes. `data0` and `data1` read the contents at
-/// those two indexes respectively, and in RAMs only, write to the contents array at those two
-/// indexes. Any read from, or write to, a data bus increments the corresponding index by 1
-/// (wrapping around to zero when incremented past 13).
+/// Internally, there's an array of ints for the contents (14 of them, as in the game, unless
+/// created with [ram_sized] or [rom_sized]), and two indexes into that array. `addr0` and `addr1`
+/// read and write those two indexes. `data0` and `data1` read the contents at those two indexes
+/// respectively, and in RAMs only, write to the contents array at those two indexes. Any read
+/// from, or write to, a data bus increments the corresponding index by 1 (wrapping around to zero
+/// when incremented past the last cell).
 pub struct Memory {
   pub addr0: XBus,
   pub addr1: XBus,
   pub data0: XBus,
   pub data1: XBus,
   mem: Arc<Mutex<MemInner>>,
+  initial: Vec<i32>,
+}
+
+impl Memory {
+  /// Return the memory to the state it was created in: both pointers back to zero, and for RAMs,
+  /// the contents cleared (or set back to the values given to [ram_with]). ROM contents can't
+  /// change, so they're left as they are.
+  ///
+  /// This must be called between calls to `Scheduler::advance`, so that it can't race with a
+  /// controller in the middle of reading or writing.
+  pub fn reset(&self) {
+    let mut mem = self.mem.lock().unwrap();
+    mem.contents.copy_from_slice(&self.initial);
+    mem.pointers = [0, 0];
+  }
+
+  /// Capture the current state of the memory: a copy of its contents, and the positions of its two
+  /// pointers. Pass these to [Memory::restore] to return to this state later.
+  pub fn snapshot(&self) -> (Vec<i32>, [usize; 2]) {
+    let mem = self.mem.lock().unwrap();
+    (mem.contents.clone(), mem.pointers)
+  }
+
+  /// Set whether the memory is strict about addresses. By default, as in the game, an address
+  /// written to `addr0` or `addr1` wraps around into range, so writing 14 to a 14-cell memory
+  /// selects cell 0. In strict mode, an address outside `0..cell_count` (including `cell_count`
+  /// itself) leaves the pointer unchanged, and the write returns `ExecError::Fault` to the
+  /// controller, which reports it to the scheduler when propagated. Pointers still wrap when
+  /// incremented by data reads and writes.
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::memory;
+  /// # use shenzhen_vm::controller::ExecError;
+  /// let ram = memory::ram_strict();
+  /// assert_eq!(ram.addr0.write(13), Ok(()));
+  /// assert!(matches!(ram.addr0.write(14), Err(ExecError::Fault(_))));
+  /// assert!(matches!(ram.addr1.write(-1), Err(ExecError::Fault(_))));
+  /// assert_eq!(ram.pointers(), [13, 0]);
+  /// ```
+  pub fn set_strict(&self, strict: bool) {
+    self.mem.lock().unwrap().strict = strict;
+  }
+
+  /// A copy of the memory's contents. Unlike reading through the data buses, this doesn't move the
+  /// pointers.
+  pub fn contents(&self) -> Vec<i32> {
+    self.mem.lock().unwrap().contents.clone()
+  }
+
+  /// The current positions of the two pointers.
+  pub fn pointers(&self) -> [usize; 2] {
+    self.mem.lock().unwrap().pointers
+  }
+
+  /// Overwrite the contents and pointers of the memory, e.g. with values from
+  /// [Memory::snapshot]. Panics if `contents` isn't the same length as the memory, or if a pointer
+  /// is out of range. Like [Memory::reset], this must be called between calls to
+  /// `Scheduler::advance`.
+  pub fn restore(&self, contents: &[i32], pointers: [usize; 2]) {
+    let mut mem = self.mem.lock().unwrap();
+    let len = mem.contents.len();
+    assert!(
+      contents.len() == len,
+      "Expected {} cells to restore, got {}",
+      len,
+      contents.len()
+    );
+    assert!(
+      pointers.iter().all(|p| *p < len),
+      "Pointers out of range for {} cells: {:?}",
+      len,
+      pointers
+    );
+
+    mem.contents.copy_from_slice(contents);
+    mem.pointers = pointers;
+  }
 }
 
 impl Debug for Memory {
@@ -94,9 +195,13 @@ impl Debug for Memory {
       ))
     };
 
-    for i in 0..7 {
+    // Lay the cells out in two columns, like the game does.
+    let rows = mem.contents.len().div_ceil(2);
+    for i in 0..rows {
       make_cell(i, f)?;
-      make_cell(i + 7, f)?;
+      if i + rows < mem.contents.len() {
+        make_cell(i + rows, f)?;
+      }
       f.write_char('\n')?;
     }
 
@@ -104,13 +209,14 @@ impl Debug for Memory {
   }
 }
 
-/// Create a ROM. The data pins don't have sinks connected, only sources, so writes to them will
-/// block forever unless there's something else reading from the same bus.
-pub fn rom(contents: [i32; 14]) -> Memory {
+fn make(contents: Vec<i32>, writable: bool) -> Memory {
+  assert!(!contents.is_empty(), "Memory must have at least one cell");
+
   let (addr0, addr1, data0, data1) = (XBus::new(), XBus::new(), XBus::new(), XBus::new());
   let mem = Arc::new(Mutex::new(MemInner {
-    contents,
+    contents: contents.clone(),
     pointers: [0, 0],
+    strict: false,
   }));
 
   let a0 = Arc::new(AddrPin {
@@ -124,10 +230,12 @@ pub fn rom(contents: [i32; 14]) -> Memory {
   let d0 = Arc::new(DataPin {
     mem: Arc::clone(&mem),
     index: 0,
+    writable,
   });
   let d1 = Arc::new(DataPin {
     mem: Arc::clone(&mem),
     index: 1,
+    writable,
   });
 
   addr0.connect_source(Arc::clone(&a0) as Arc<AddrPin>);
@@ -135,8 +243,10 @@ pub fn rom(contents: [i32; 14]) -> Memory {
   addr1.connect_source(Arc::clone(&a1) as Arc<AddrPin>);
   addr1.connect_sink(a1);
 
-  data0.connect_source(d0);
-  data1.connect_source(d1);
+  data0.connect_source(Arc::clone(&d0) as Arc<DataPin>);
+  data0.connect_sink(d0);
+  data1.connect_source(Arc::clone(&d1) as Arc<DataPin>);
+  data1.connect_sink(d1);
 
   Memory {
     addr0,
@@ -144,49 +254,73 @@ pub fn rom(contents: [i32; 14]) -> Memory {
     data0,
     data1,
     mem,
+    initial: contents,
   }
 }
 
+/// Create a ROM. Writing to its data pins is always a bug, so unless there's something else
+/// reading from the same bus to take the value, a write returns `ExecError::Fault` naming the
+/// controller that did it and the pin. The value is discarded.
+///
+/// ```
+/// # use shenzhen_vm::components::memory;
+/// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+/// # use shenzhen_vm::scheduler::{sleep, Scheduler};
+/// # use shenzhen_vm::xbus::XBus;
+/// struct Writer(XBus);
+/// impl Controller for Writer {
+///   fn name(&self) -> &'static str { "writer" }
+///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+///     self.0.write(5)?;
+///     sleep(1)
+///   }
+/// }
+///
+/// let rom = memory::rom([0; 14]);
+/// let mut scheduler = Scheduler::new(vec![Box::new(Writer(rom.data1.clone()))]);
+/// scheduler.advance().unwrap();
+/// assert_eq!(
+///   scheduler.faults()["writer"],
+///   "Write of 5 to ROM data pin 1 from controller writer"
+/// );
+/// # scheduler.end();
+/// ```
+pub fn rom(contents: [i32; 14]) -> Memory {
+  rom_sized(contents.to_vec())
+}
+
+/// Create a ROM with as many cells as there are values in `contents`. Otherwise, it behaves just
+/// like one created with [rom]. Panics if `contents` is empty.
+pub fn rom_sized(contents: Vec<i32>) -> Memory {
+  make(contents, false)
+}
+
 /// Create a RAM, initialized to all zeros.
 pub fn ram() -> Memory {
-  let (addr0, addr1, data0, data1) = (XBus::new(), XBus::new(), XBus::new(), XBus::new());
-  let mem = Arc::new(Mutex::new(MemInner {
-    contents: [0; 14],
-    pointers: [0, 0],
-  }));
+  ram_sized(14)
+}
 
-  let a0 = Arc::new(AddrPin {
-    mem: Arc::clone(&mem),
-    index: 0,
-  });
-  let a1 = Arc::new(AddrPin {
-    mem: Arc::clone(&mem),
-    index: 1,
-  });
-  let d0 = Arc::new(DataPin {
-    mem: Arc::clone(&mem),
-    index: 0,
-  });
-  let d1 = Arc::new(DataPin {
-    mem: Arc::clone(&mem),
-    index: 1,
-  });
+/// Create a RAM, initialized to the given contents. Otherwise, it behaves just like one created
+/// with [ram].
+pub fn ram_with(contents: [i32; 14]) -> Memory {
+  make(contents.to_vec(), true)
+}
 
-  addr0.connect_source(Arc::clone(&a0) as Arc<AddrPin>);
-  addr0.connect_sink(a0);
-  addr1.connect_source(Arc::clone(&a1) as Arc<AddrPin>);
-  addr1.connect_sink(a1);
+/// Create a ROM like [rom], but in strict mode; see [Memory::set_strict].
+pub fn rom_strict(contents: [i32; 14]) -> Memory {
+  let memory = rom(contents);
+  memory.set_strict(true);
+  memory
+}
 
-  data0.connect_source(Arc::clone(&d0) as Arc<DataPin>);
-  data0.connect_sink(d0);
-  data1.connect_source(Arc::clone(&d1) as Arc<DataPin>);
-  data1.connect_sink(d1);
+/// Create a RAM like [ram], but in strict mode; see [Memory::set_strict].
+pub fn ram_strict() -> Memory {
+  let memory = ram();
+  memory.set_strict(true);
+  memory
+}
 
-  Memory {
-    addr0,
-    addr1,
-    data0,
-    data1,
-    mem,
-  }
+/// Create a RAM with `size` cells, initialized to all zeros. Panics if `size` is zero.
+pub fn ram_sized(size: usize) -> Memory {
+  make(vec![0; size], true)
 }
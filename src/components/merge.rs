@@ -0,0 +1,78 @@
+//! A merger that forwards values from several XBuses onto one, implemented as a controller.
+
+use crate::controller::{use_power, Controller, ExecError, Regs};
+use crate::scheduler::{sleep, Scheduler, SleepToken};
+use crate::xbus::XBus;
+
+struct Merge {
+  name: &'static str,
+  inputs: Vec<XBus>,
+  output: XBus,
+}
+
+/// Creates a merger: a controller (to pass to `Scheduler::new`) that forwards values from any of
+/// the `inputs` buses to `output`, one value per timestep. While no input has a value, it sleeps
+/// until one does, as with `XBus::sleep`. `name` must be unique among the scheduler's controllers.
+/// Panics if `inputs` is empty.
+///
+/// When several inputs have a value at once, they take turns: the merger starts looking from the
+/// input after the one it last forwarded from, so a busy input can't starve the others. Unlike
+/// connecting several sources to one bus, which always reads the first that has a value, this is
+/// fair. As with any controller, the write blocks until something consumes the value.
+///
+/// ```
+/// # use shenzhen_vm::components::{inputsource, merge, outputsink};
+/// # use shenzhen_vm::scheduler::Scheduler;
+/// let (_, a) = inputsource::from_iter(true, [1, 2, 3]);
+/// let (_, b) = inputsource::from_iter(true, [10, 20]);
+/// let (sink, output) = outputsink::new("out", false);
+/// let mut scheduler = Scheduler::new(vec![merge::new("merge", vec![a, b], output)]);
+/// scheduler.advance_n(6).unwrap();
+/// # scheduler.end();
+/// assert_eq!(sink.peek_all(), vec![1, 10, 2, 20, 3]);
+/// ```
+pub fn new(name: &'static str, inputs: Vec<XBus>, output: XBus) -> Box<dyn Controller + Send> {
+  assert!(!inputs.is_empty(), "Merger needs at least one input");
+  Box::new(Merge {
+    name,
+    inputs,
+    output,
+  })
+}
+
+impl Merge {
+  /// Sleep until any of the inputs is readable.
+  fn sleep_any(&self) -> Result<(), ExecError> {
+    use_power();
+    let token = self
+      .inputs
+      .iter()
+      .map(|bus| SleepToken::XBusSleep(bus.clone()))
+      .reduce(|a, b| SleepToken::Either(Box::new(a), Box::new(b)))
+      .expect("");
+    Scheduler::sleep(token)
+  }
+}
+
+impl Controller for Merge {
+  fn name(&self) -> &'static str {
+    self.name
+  }
+
+  // acc holds the index of the input to look at first.
+  fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
+    let count = self.inputs.len() as i32;
+    let ready = (0..count)
+      .map(|i| (reg.acc + i) % count)
+      .find(|&index| self.inputs[index as usize].can_read());
+
+    match ready {
+      Some(index) => {
+        self.output.write(self.inputs[index as usize].read()?)?;
+        reg.acc = (index + 1) % count;
+        sleep(1)
+      }
+      None => self.sleep_any(),
+    }
+  }
+}
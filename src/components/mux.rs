@@ -0,0 +1,70 @@
+//! A multiplexer that routes one of several XBuses to another, implemented as a controller.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use crate::controller::{Controller, ExecError, Regs};
+use crate::scheduler::sleep;
+use crate::xbus::XBus;
+
+struct Mux {
+  name: &'static str,
+  selector: Arc<AtomicI32>,
+  inputs: Vec<XBus>,
+  output: XBus,
+}
+
+/// Creates a multiplexer: a controller (to pass to `Scheduler::new`) that once per timestep reads
+/// `selector`, reads one value from the input bus at that index, and writes it to `output`.
+/// Selectors out of range are clamped to the first or last input. `name` must be unique among the
+/// scheduler's controllers. Panics if `inputs` is empty.
+///
+/// As with any controller, the read and the write block until they can complete, so the selected
+/// input must have a value available, and something must consume the output, in every timestep.
+///
+/// ```
+/// # use shenzhen_vm::components::{inputsource, mux, outputsink};
+/// # use shenzhen_vm::scheduler::Scheduler;
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// let (_, a) = inputsource::from_iter(true, [1, 2, 3]);
+/// let (_, b) = inputsource::from_iter(true, [10, 20, 30]);
+/// let (sink, output) = outputsink::new("out", false);
+/// let selector = Arc::new(AtomicI32::new(0));
+/// let mut scheduler = Scheduler::new(vec![mux::new("mux", selector.clone(), vec![a, b], output)]);
+///
+/// // The last selector is out of range, so it's clamped to the second input.
+/// for select in [0, 1, 1, 0, 5] {
+///   selector.store(select, Ordering::Relaxed);
+///   scheduler.advance().unwrap();
+/// }
+/// # scheduler.end();
+/// assert_eq!(sink.peek_all(), vec![1, 10, 20, 2, 30]);
+/// ```
+pub fn new(
+  name: &'static str,
+  selector: Arc<AtomicI32>,
+  inputs: Vec<XBus>,
+  output: XBus,
+) -> Box<dyn Controller + Send> {
+  assert!(!inputs.is_empty(), "Multiplexer needs at least one input");
+  Box::new(Mux {
+    name,
+    selector,
+    inputs,
+    output,
+  })
+}
+
+impl Controller for Mux {
+  fn name(&self) -> &'static str {
+    self.name
+  }
+
+  fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+    let last = self.inputs.len() as i32 - 1;
+    let index = self.selector.load(Ordering::Relaxed).clamp(0, last);
+    self.output.write(self.inputs[index as usize].read()?)?;
+    sleep(1)
+  }
+}
@@ -1,39 +1,275 @@
 //! For printing program output, and storing it for verification.
 
 use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
 
 use crate::xbus::{TSink, XBus};
 
+/// What a bounded [OutputSink] does when a value is written while its queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+  /// Remove the oldest value from the queue to make room for the new one.
+  DropOldest,
+  /// Discard the new value.
+  DropNewest,
+  /// Discard the new value, and make the next `queue_into` call return an [OverflowError].
+  Error,
+}
+
+/// Returned by [OutputSink::queue_into] when a sink with [OverflowPolicy::Error] overflowed.
+#[derive(Debug)]
+pub struct OverflowError {
+  pub name: &'static str,
+  /// The number of values discarded since the last `queue_into` call.
+  pub dropped: usize,
+}
+
+impl Error for OverflowError {}
+
+impl std::fmt::Display for OverflowError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "Output sink '{}' overflowed, dropping {} values",
+      self.name, self.dropped
+    )
+  }
+}
+
+/// Summary statistics over the values in an [OutputSink]'s queue; see [OutputSink::stats].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SinkStats {
+  pub count: usize,
+  pub min: i32,
+  pub max: i32,
+  pub sum: i64,
+  pub last: i32,
+}
+
+type Callback = Box<dyn FnMut(i32) + Send>;
+
+/// A record of the values written to several [OutputSink]s, in the order they were written, for
+/// checking the order of writes across buses. Attach sinks to it with [OutputSink::log_to].
+#[derive(Default)]
+pub struct WriteLog {
+  events: Mutex<Vec<(&'static str, i32)>>,
+}
+
+impl WriteLog {
+  /// Create an empty log.
+  pub fn new() -> Arc<WriteLog> {
+    Arc::new(WriteLog::default())
+  }
+
+  /// Remove and return the logged writes, as (sink name, value) pairs in the order they happened.
+  pub fn take(&self) -> Vec<(&'static str, i32)> {
+    std::mem::take(&mut *self.events.lock().unwrap())
+  }
+}
+
 pub struct OutputSink {
   name: &'static str,
   printing: bool,
   queue: Mutex<VecDeque<i32>>,
+  capacity: Option<usize>,
+  policy: OverflowPolicy,
+  dropped: AtomicUsize,
+  callback: Option<Mutex<Callback>>,
+  log: Mutex<Option<Arc<WriteLog>>>,
 }
 
-/// Create a new sink, returning it and an XBus that it's connected to. If `printing` is true,
-/// each value written will be printed with `println!`.
-pub fn new(name: &'static str, printing: bool) -> (Arc<OutputSink>, XBus) {
+fn make(
+  name: &'static str,
+  printing: bool,
+  capacity: Option<usize>,
+  policy: OverflowPolicy,
+  callback: Option<Callback>,
+) -> (Arc<OutputSink>, XBus) {
   let xbus = XBus::new();
   let sink = Arc::new(OutputSink {
     name,
     printing,
     queue: Mutex::new(VecDeque::new()),
+    capacity,
+    policy,
+    dropped: AtomicUsize::new(0),
+    callback: callback.map(Mutex::new),
+    log: Mutex::new(None),
   });
 
   xbus.connect_sink(Arc::clone(&sink) as Arc<OutputSink>);
   (sink, xbus)
 }
 
+/// Create a new sink, returning it and an XBus that it's connected to. If `printing` is true,
+/// each value written will be printed with `println!`.
+pub fn new(name: &'static str, printing: bool) -> (Arc<OutputSink>, XBus) {
+  make(name, printing, None, OverflowPolicy::DropNewest, None)
+}
+
+/// Create a new sink like [new], but whose queue holds at most `capacity` values. When a value is
+/// written while the queue is full, `policy` decides what happens. Panics if `capacity` is 0.
+pub fn bounded(
+  name: &'static str,
+  printing: bool,
+  capacity: usize,
+  policy: OverflowPolicy,
+) -> (Arc<OutputSink>, XBus) {
+  assert!(capacity > 0, "Bounded sink needs a capacity of at least 1");
+  make(name, printing, Some(capacity), policy, None)
+}
+
+/// Create a new sink like [new], without printing, but calling `callback` with each value as it's
+/// written, in addition to queueing it.
+///
+/// The callback runs on the thread of the controller doing the write, in the middle of its
+/// `XBus::write` call, so it should be quick and must not block on anything that waits for the
+/// scheduler. Sending to an `mpsc` channel is fine.
+pub fn with_callback(name: &'static str, callback: Callback) -> (Arc<OutputSink>, XBus) {
+  make(
+    name,
+    false,
+    None,
+    OverflowPolicy::DropNewest,
+    Some(callback),
+  )
+}
+
 impl OutputSink {
   /// Move the contents of the internal queue into the given Vec.
-  pub fn queue_into(&self, dest: &mut Vec<i32>) {
+  ///
+  /// If this is a bounded sink with [OverflowPolicy::Error], and values have been discarded since
+  /// the last call, the contents are still moved, but an error is returned.
+  pub fn queue_into(&self, dest: &mut Vec<i32>) -> Result<(), OverflowError> {
     let mut queue = self.queue.lock().unwrap();
 
     while !queue.is_empty() {
       dest.push(queue.pop_front().expect(""));
     }
+
+    match self.dropped.swap(0, Ordering::Relaxed) {
+      0 => Ok(()),
+      dropped => Err(OverflowError {
+        name: self.name,
+        dropped,
+      }),
+    }
+  }
+
+  /// Move up to `max` values from the front of the internal queue into the given Vec, leaving the
+  /// rest queued, and return how many were moved. This is useful for modeling a consumer that only
+  /// handles a limited number of outputs per timestep.
+  ///
+  /// Unlike [OutputSink::queue_into], this doesn't report or clear the count of values discarded
+  /// by a bounded sink.
+  pub fn drain_into(&self, dest: &mut Vec<i32>, max: usize) -> usize {
+    let mut queue = self.queue.lock().unwrap();
+    let count = max.min(queue.len());
+    dest.extend(queue.drain(..count));
+    count
+  }
+
+  /// Move the contents of the internal queue to `w`, as a single line of comma-separated values,
+  /// like a row of [crate::filerunner::FileRunner] data. An empty queue writes an empty line.
+  ///
+  /// If this is a bounded sink with [OverflowPolicy::Error], and values have been discarded since
+  /// the last call, the contents are still written, but an error wrapping an [OverflowError] is
+  /// returned.
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::outputsink;
+  /// let (sink, bus) = outputsink::new("out", false);
+  /// for value in [3, -1, 4] {
+  ///   bus.write(value).unwrap();
+  /// }
+  /// let mut csv = Vec::new();
+  /// sink.write_csv(&mut csv).unwrap();
+  /// assert_eq!(csv, b"3,-1,4\n");
+  /// assert!(sink.is_empty());
+  /// ```
+  pub fn write_csv(&self, w: &mut dyn Write) -> io::Result<()> {
+    let mut values = Vec::new();
+    let result = self.queue_into(&mut values);
+    let strings: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    writeln!(w, "{}", strings.join(","))?;
+    result.map_err(io::Error::other)
+  }
+
+  /// Move the contents of the internal queue to `w` like [OutputSink::write_csv], but with each
+  /// value on its own line. An empty queue writes nothing.
+  pub fn write_lines(&self, w: &mut dyn Write) -> io::Result<()> {
+    let mut values = Vec::new();
+    let result = self.queue_into(&mut values);
+    for value in values {
+      writeln!(w, "{}", value)?;
+    }
+    result.map_err(io::Error::other)
+  }
+
+  /// Also record every value written to this sink in `log`, along with the sink's name. Values are
+  /// logged as they're written, even if a bounded sink then discards them.
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::outputsink::{self, WriteLog};
+  /// let log = WriteLog::new();
+  /// let (a, a_bus) = outputsink::new("a", false);
+  /// let (b, b_bus) = outputsink::new("b", false);
+  /// a.log_to(&log);
+  /// b.log_to(&log);
+  /// a_bus.write(1).unwrap();
+  /// b_bus.write(2).unwrap();
+  /// a_bus.write(3).unwrap();
+  /// assert_eq!(log.take(), vec![("a", 1), ("b", 2), ("a", 3)]);
+  /// ```
+  pub fn log_to(&self, log: &Arc<WriteLog>) {
+    *self.log.lock().unwrap() = Some(Arc::clone(log));
+  }
+
+  /// Return a copy of the contents of the internal queue, without removing them.
+  pub fn peek_all(&self) -> Vec<i32> {
+    self.queue.lock().unwrap().iter().copied().collect()
+  }
+
+  /// The number of values in the internal queue.
+  pub fn len(&self) -> usize {
+    self.queue.lock().unwrap().len()
+  }
+
+  /// Whether the internal queue is empty.
+  pub fn is_empty(&self) -> bool {
+    self.queue.lock().unwrap().is_empty()
+  }
+
+  /// The most recently written value still in the internal queue, if any.
+  pub fn last(&self) -> Option<i32> {
+    self.queue.lock().unwrap().back().copied()
+  }
+
+  /// Summary statistics over the values in the internal queue, or `None` if it's empty.
+  pub fn stats(&self) -> Option<SinkStats> {
+    let queue = self.queue.lock().unwrap();
+    let first = *queue.front()?;
+
+    let mut stats = SinkStats {
+      count: 0,
+      min: first,
+      max: first,
+      sum: 0,
+      last: first,
+    };
+    for &val in queue.iter() {
+      stats.count += 1;
+      stats.min = stats.min.min(val);
+      stats.max = stats.max.max(val);
+      stats.sum += val as i64;
+      stats.last = val;
+    }
+
+    Some(stats)
   }
 }
 
@@ -43,6 +279,27 @@ impl TSink for OutputSink {
       println!("{}: {}", self.name, val)
     }
 
-    self.queue.lock().unwrap().push_back(val);
+    if let Some(callback) = &self.callback {
+      (callback.lock().unwrap())(val);
+    }
+
+    if let Some(log) = self.log.lock().unwrap().as_ref() {
+      log.events.lock().unwrap().push((self.name, val));
+    }
+
+    let mut queue = self.queue.lock().unwrap();
+    if self.capacity.is_some_and(|cap| queue.len() >= cap) {
+      match self.policy {
+        OverflowPolicy::DropOldest => {
+          queue.pop_front();
+        }
+        OverflowPolicy::DropNewest => return,
+        OverflowPolicy::Error => {
+          self.dropped.fetch_add(1, Ordering::Relaxed);
+          return;
+        }
+      }
+    }
+    queue.push_back(val);
   }
 }
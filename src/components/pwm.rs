@@ -0,0 +1,88 @@
+//! A pulse-width modulator that drives a simple I/O pin from a duty cycle written over an XBus,
+//! implemented as a controller.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use crate::controller::{Controller, ExecError, Regs};
+use crate::scheduler::sleep;
+use crate::xbus::{TSink, XBus};
+
+/// The duty cycle most recently written, clamped to `0..=period`.
+struct Duty {
+  value: AtomicI32,
+  period: i32,
+}
+
+impl TSink for Duty {
+  fn write(&self, val: i32) {
+    self
+      .value
+      .store(val.clamp(0, self.period), Ordering::Relaxed);
+  }
+}
+
+struct Pwm {
+  name: &'static str,
+  duty: Arc<Duty>,
+  pin: Arc<AtomicI32>,
+}
+
+/// Creates a pulse-width modulator: a controller (to pass to `Scheduler::new`) that repeatedly
+/// sets `pin` to 100 for `duty` timesteps and then to 0 for the rest of each `period` timesteps,
+/// and the XBus to write `duty` to. Writes never block, and values outside `0..=period` are
+/// clamped. A new duty cycle takes effect at the start of the next period, so each period is a
+/// clean pulse. The duty cycle starts at 0. `name` must be unique among the scheduler's
+/// controllers. Panics if `period` is 0.
+///
+/// ```
+/// # use shenzhen_vm::components::pwm;
+/// # use shenzhen_vm::scheduler::Scheduler;
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// let pin = Arc::new(AtomicI32::new(0));
+/// let (ctrl, duty) = pwm::new("pwm", pin.clone(), 4);
+/// duty.write(3).unwrap();
+/// let mut scheduler = Scheduler::new(vec![ctrl]);
+/// let levels: Vec<i32> = (0..8)
+///   .map(|_| {
+///     scheduler.advance().unwrap();
+///     pin.load(Ordering::Relaxed)
+///   })
+///   .collect();
+/// assert_eq!(levels, [100, 100, 100, 0, 100, 100, 100, 0]);
+/// # scheduler.end();
+/// ```
+pub fn new(
+  name: &'static str,
+  pin: Arc<AtomicI32>,
+  period: u32,
+) -> (Box<dyn Controller + Send>, XBus) {
+  assert!(period > 0, "PWM period must be positive");
+
+  let duty = Arc::new(Duty {
+    value: AtomicI32::new(0),
+    period: period as i32,
+  });
+  let xbus = XBus::new();
+  xbus.connect_sink(Arc::clone(&duty) as Arc<Duty>);
+
+  (Box::new(Pwm { name, duty, pin }), xbus)
+}
+
+impl Controller for Pwm {
+  fn name(&self) -> &'static str {
+    self.name
+  }
+
+  // acc counts timesteps within the period, and dat holds the period's duty cycle.
+  fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
+    if reg.acc == 0 {
+      reg.dat = self.duty.value.load(Ordering::Relaxed);
+    }
+    let level = if reg.acc < reg.dat { 100 } else { 0 };
+    self.pin.store(level, Ordering::Relaxed);
+    reg.acc = (reg.acc + 1) % self.duty.period;
+    sleep(1)
+  }
+}
@@ -0,0 +1,35 @@
+//! A sensor whose reading changes over time according to a script.
+
+use std::sync::Arc;
+
+use crate::scheduler::current_time;
+use crate::xbus::{TSource, XBus};
+
+struct Sensor {
+  /// (timestep, value) pairs, sorted by timestep.
+  readings: Vec<(u32, i32)>,
+}
+
+/// Creates a sensor and returns the XBus it's connected to. Each element of `readings` is a
+/// `(timestep, value)` pair; reading from the XBus produces the value of the latest pair whose
+/// timestep is at or before the current one, or 0 if there isn't one yet. The sensor is always
+/// readable. The current timestep comes from the scheduler of the controller doing the read.
+pub fn new(mut readings: Vec<(u32, i32)>) -> XBus {
+  readings.sort_by_key(|(time, _)| *time);
+
+  let xbus = XBus::new();
+  xbus.connect_source(Arc::new(Sensor { readings }));
+  xbus
+}
+
+impl TSource for Sensor {
+  fn can_read(&self) -> bool {
+    true
+  }
+
+  fn read(&self) -> i32 {
+    let now = current_time();
+    let count = self.readings.partition_point(|(time, _)| *time <= now);
+    count.checked_sub(1).map_or(0, |i| self.readings[i].1)
+  }
+}
@@ -0,0 +1,97 @@
+//! A shift register for converting a serial stream of bits to a parallel value.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use crate::xbus::{TSink, TSource, XBus};
+
+/// Holds the last `width` bits written to it. Writing a value to its XBus shifts the existing bits
+/// left by one, dropping the oldest, and shifts in 1 if the value is nonzero or 0 otherwise, so
+/// that the game's usual high level of 100 counts as a 1. Reading from the XBus returns the bits
+/// packed into a number, with the newest bit as the lowest, without consuming them. Like a latch,
+/// it's always readable.
+///
+/// To stay within the game's range of values, keep `width` at 9 or less.
+///
+/// ```
+/// # use shenzhen_vm::components::shiftregister;
+/// let (register, bus) = shiftregister::new(4);
+/// for value in [100, 0, 100, 100, 0] {
+///   bus.write(value).unwrap();
+/// }
+/// assert_eq!(register.value(), 0b0110);
+/// assert_eq!(bus.read(), Ok(0b0110));
+/// assert_eq!(format!("{:?}", register), "ShiftRegister { bits: 0110 }");
+/// ```
+pub struct ShiftRegister {
+  bits: AtomicI32,
+  width: usize,
+}
+
+/// Create a shift register holding `width` bits, all initially 0, returning it and the XBus it's
+/// connected to. Panics if `width` isn't between 1 and 31.
+pub fn new(width: usize) -> (Arc<ShiftRegister>, XBus) {
+  assert!(
+    (1..=31).contains(&width),
+    "Shift register width must be between 1 and 31"
+  );
+
+  let xbus = XBus::new();
+  let register = Arc::new(ShiftRegister {
+    bits: AtomicI32::new(0),
+    width,
+  });
+  xbus.connect_sink(Arc::clone(&register) as Arc<ShiftRegister>);
+  xbus.connect_source(Arc::clone(&register) as Arc<ShiftRegister>);
+  (register, xbus)
+}
+
+impl ShiftRegister {
+  /// The current contents, packed into a number with the newest bit as the lowest.
+  pub fn value(&self) -> i32 {
+    self.bits.load(Ordering::Relaxed)
+  }
+
+  /// The number of bits held.
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  /// Set all the bits to 0.
+  pub fn clear(&self) {
+    self.bits.store(0, Ordering::Relaxed);
+  }
+}
+
+impl TSource for ShiftRegister {
+  fn can_read(&self) -> bool {
+    true
+  }
+
+  fn read(&self) -> i32 {
+    self.value()
+  }
+}
+
+impl TSink for ShiftRegister {
+  fn write(&self, val: i32) {
+    let mask = ((1u32 << self.width) - 1) as i32;
+    let bit = (val != 0) as i32;
+    let _ = self
+      .bits
+      .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+        Some(((bits << 1) | bit) & mask)
+      });
+  }
+}
+
+impl Debug for ShiftRegister {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // Oldest bit first.
+    let width = self.width;
+    f.debug_struct("ShiftRegister")
+      .field("bits", &format_args!("{:0width$b}", self.value()))
+      .finish()
+  }
+}
@@ -0,0 +1,78 @@
+//! Helpers for simple I/O pins.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Records the values a simple I/O pin takes, rather than only its latest value, so that a pulse
+/// within a timestep can be checked. Register it with [crate::scheduler::Scheduler::log_pin]; the
+/// scheduler then samples the pin each time a controller goes back to sleep.
+///
+/// Only changes between samples are seen, so a controller that sets the pin and changes it again
+/// without sleeping in between only shows the second value.
+pub struct LoggedPin {
+  name: &'static str,
+  pin: Arc<AtomicI32>,
+  /// The last value sampled, and the changes recorded since the last call to `take`.
+  state: Mutex<(i32, Vec<i32>)>,
+}
+
+/// Create a logged pin, returning it and the pin itself, which is used like any other simple I/O
+/// pin. The pin starts at 0.
+///
+/// ```
+/// # use shenzhen_vm::components::simple;
+/// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+/// # use shenzhen_vm::scheduler::{sleep, Scheduler};
+/// # use std::sync::atomic::{AtomicI32, Ordering};
+/// # use std::sync::Arc;
+/// struct Pulser(Arc<AtomicI32>);
+/// impl Controller for Pulser {
+///   fn name(&self) -> &'static str { "pulser" }
+///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+///     self.0.store(100, Ordering::Relaxed);
+///     sleep(0)?;
+///     self.0.store(0, Ordering::Relaxed);
+///     sleep(1)
+///   }
+/// }
+///
+/// let (logged, pin) = simple::logged("pulse");
+/// let mut scheduler = Scheduler::new(vec![Box::new(Pulser(pin.clone()))]);
+/// scheduler.log_pin(logged.clone());
+/// scheduler.advance().unwrap();
+/// // The pin ends the timestep at 0, but the log shows the pulse.
+/// assert_eq!(pin.load(Ordering::Relaxed), 0);
+/// assert_eq!(logged.take(), vec![100, 0]);
+/// # scheduler.end();
+/// ```
+pub fn logged(name: &'static str) -> (Arc<LoggedPin>, Arc<AtomicI32>) {
+  let pin = Arc::new(AtomicI32::new(0));
+  let logged = Arc::new(LoggedPin {
+    name,
+    pin: Arc::clone(&pin),
+    state: Mutex::new((0, vec![])),
+  });
+  (logged, pin)
+}
+
+impl LoggedPin {
+  pub fn name(&self) -> &'static str {
+    self.name
+  }
+
+  /// Remove and return the values the pin has changed to since the last call, in order. If the
+  /// pin hasn't changed, this is empty.
+  pub fn take(&self) -> Vec<i32> {
+    std::mem::take(&mut self.state.lock().unwrap().1)
+  }
+
+  /// Record the pin's current value if it has changed since the last sample.
+  pub(crate) fn sample(&self) {
+    let value = self.pin.load(Ordering::Relaxed);
+    let (last, changes) = &mut *self.state.lock().unwrap();
+    if value != *last {
+      *last = value;
+      changes.push(value);
+    }
+  }
+}
@@ -0,0 +1,104 @@
+//! A LIFO stack component, read and written over a single XBus.
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use crate::xbus::{TSink, TSource, XBus};
+
+struct StackInner {
+  contents: Mutex<Vec<i32>>,
+  capacity: usize,
+}
+
+/// Represents a stack of values with a fixed capacity.
+///
+/// Writing to `data` pushes a value onto the stack, and reading from it pops the top value.
+/// Reading from an empty stack blocks until something is pushed, and pushing onto a full stack
+/// blocks until something is popped. As with other blocking XBuses, use `XBus::sleep` to wait for
+/// the stack to be nonempty before popping.
+pub struct Stack {
+  pub data: XBus,
+  inner: Arc<StackInner>,
+}
+
+/// Create an empty stack that holds up to `capacity` values.
+///
+/// ```
+/// # use shenzhen_vm::components::stack;
+/// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+/// # use shenzhen_vm::scheduler::{sleep, AdvanceError, Scheduler};
+/// # use shenzhen_vm::xbus::XBus;
+/// struct Pusher(XBus);
+/// impl Controller for Pusher {
+///   fn name(&self) -> &'static str { "pusher" }
+///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+///     for value in 1..=4 {
+///       self.0.write(value)?;
+///     }
+///     sleep(1)
+///   }
+/// }
+///
+/// let stack = stack::new(3);
+/// let mut scheduler = Scheduler::new(vec![Box::new(Pusher(stack.data.clone()))]);
+/// for _ in 0..2 {
+///   // The fourth push blocks, since the stack is full.
+///   assert!(matches!(scheduler.advance(), Err(AdvanceError::Deadlock(_))));
+///   assert_eq!(format!("{:?}", stack), "[3, 2, 1]");
+///
+///   // Popping takes the blocked value first, then the rest from the top down.
+///   let popped: Vec<i32> = (0..4).map(|_| stack.data.read().unwrap()).collect();
+///   assert_eq!(popped, vec![4, 3, 2, 1]);
+///   assert_eq!(format!("{:?}", stack), "[]");
+///
+///   // The push completes, and the pusher fills the stack again in the next timestep.
+///   scheduler.advance().unwrap();
+/// }
+/// # scheduler.end();
+/// ```
+pub fn new(capacity: usize) -> Stack {
+  let data = XBus::new();
+  let inner = Arc::new(StackInner {
+    contents: Mutex::new(Vec::with_capacity(capacity)),
+    capacity,
+  });
+
+  data.connect_source(Arc::clone(&inner) as Arc<StackInner>);
+  data.connect_sink(Arc::clone(&inner) as Arc<StackInner>);
+
+  Stack { data, inner }
+}
+
+impl TSource for StackInner {
+  fn can_read(&self) -> bool {
+    !self.contents.lock().unwrap().is_empty()
+  }
+
+  fn read(&self) -> i32 {
+    self
+      .contents
+      .lock()
+      .unwrap()
+      .pop()
+      .expect("Cannot pop from empty stack")
+  }
+}
+
+impl TSink for StackInner {
+  fn can_write(&self) -> bool {
+    self.contents.lock().unwrap().len() < self.capacity
+  }
+
+  fn write(&self, val: i32) {
+    self.contents.lock().unwrap().push(val);
+  }
+}
+
+impl Debug for Stack {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // Top of the stack first.
+    f.debug_list()
+      .entries(self.inner.contents.lock().unwrap().iter().rev())
+      .finish()
+  }
+}
@@ -1,41 +1,405 @@
 //! A trait representing controllers, plus a few macros mimicking complex game instructions.
 
-use std::cell::RefCell;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
 use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
 use std::thread;
 
-use crate::scheduler::{Scheduler, SleepMessage, SleepToken};
+use crate::scheduler::{Scheduler, SharedBuses, SharedRegs, SleepMessage, SleepToken};
+use crate::trace::{TraceEvent, TraceOp, Tracer};
+use crate::xbus::XBus;
 
 /// A controller's state that persists across repeated executions of its `execute` function.
+///
+/// The `acc` and `dat` fields can be used freely, with full 32-bit arithmetic. The [Regs::add],
+/// [Regs::sub], [Regs::mul], and [Regs::not] helpers instead enforce the game's semantics.
+///
+/// Besides `acc` and `dat`, this holds the flag set by the game's test instructions (`teq`, `tgt`,
+/// `tlt`, `tcp`), which decides whether `+` and `-` instructions run. Use [Regs::if_plus] and
+/// [Regs::if_minus] to guard code that would be conditionally executed in the game.
+///
+/// Controllers that need more state than the game allows, while still in a freeform phase, can
+/// keep extra named registers with [Regs::get_reg] and [Regs::set_reg].
+///
+/// With the `serde` feature, `Regs` can be serialized and deserialized, e.g. to checkpoint a
+/// controller's state and restore it with [Controller::initial_regs]. Deserializing doesn't intern
+/// the names of extra registers, so untrusted data can't leak memory through them.
+///
+/// ```
+/// # #[cfg(feature = "serde")] {
+/// # use shenzhen_vm::controller::Regs;
+/// let mut reg = Regs::new(5, -3).with_reg("count", 12);
+/// reg.tgt(reg.acc, 0);
+/// let json = serde_json::to_string(&reg).unwrap();
+/// let restored: Regs = serde_json::from_str(&json).unwrap();
+/// assert_eq!((restored.acc, restored.dat, restored.get_reg("count")), (5, -3, 12));
+/// assert!(restored.if_plus());
+/// # }
+/// ```
 #[derive(Debug)]
 pub struct Regs {
   pub acc: i32,
   pub dat: i32,
+  test: cmp::Ordering,
+  /// Owned names only come from deserializing.
+  extra: HashMap<Cow<'static, str>, i32>,
+}
+
+impl Default for Regs {
+  fn default() -> Self {
+    Regs {
+      acc: 0,
+      dat: 0,
+      test: cmp::Ordering::Equal,
+      extra: HashMap::new(),
+    }
+  }
+}
+
+/// The smallest value a register can hold in the game.
+pub const MIN_VALUE: i32 = -999;
+
+/// The largest value a register can hold in the game.
+pub const MAX_VALUE: i32 = 999;
+
+fn clamp(value: i32) -> i32 {
+  value.clamp(MIN_VALUE, MAX_VALUE)
+}
+
+/// Pack up to three single-digit values into the decimal digits of one number, the first value
+/// becoming the most significant digit, like repeating `acc *= 10; acc += value` in the game. The
+/// result always fits in a register. Use [unpack_digits] to reverse it.
+///
+/// Panics if there are more than three values, or any value is outside `0..=9`, rather than
+/// silently producing a number that doesn't unpack to the same values.
+///
+/// ```
+/// # use shenzhen_vm::controller::{pack_digits, unpack_digits};
+/// assert_eq!(pack_digits(&[4, 0, 7]), 407);
+/// assert_eq!(pack_digits(&[0, 5]), 5);
+/// assert_eq!(pack_digits(&[]), 0);
+/// assert_eq!(unpack_digits(407, 3), vec![4, 0, 7]);
+/// assert_eq!(unpack_digits(5, 2), vec![0, 5]);
+/// assert_eq!(unpack_digits(999, 3), vec![9, 9, 9]);
+/// ```
+///
+/// ```should_panic
+/// # use shenzhen_vm::controller::pack_digits;
+/// pack_digits(&[1, 10]);
+/// ```
+pub fn pack_digits(values: &[i32]) -> i32 {
+  assert!(
+    values.len() <= 3,
+    "Can only pack up to 3 digits, got {}",
+    values.len()
+  );
+  values.iter().fold(0, |packed, value| {
+    assert!(
+      (0..=9).contains(value),
+      "Packed values must be between 0 and 9, got {}",
+      value
+    );
+    packed * 10 + value
+  })
+}
+
+/// Unpack `count` values from the decimal digits of `packed`, most significant first, reversing
+/// [pack_digits].
+///
+/// Panics if `count` is more than three, or `packed` is negative or has more than `count` digits.
+pub fn unpack_digits(packed: i32, count: usize) -> Vec<i32> {
+  assert!(count <= 3, "Can only unpack up to 3 digits, got {}", count);
+  assert!(
+    packed >= 0 && packed < 10_i32.pow(count as u32),
+    "{} can't be unpacked into {} digits",
+    packed,
+    count
+  );
+  (0..count)
+    .rev()
+    .map(|index| (packed / 10_i32.pow(index as u32)) % 10)
+    .collect()
 }
 
 impl Regs {
+  /// Create registers with the given values of acc and dat, for [Controller::initial_regs]. Use
+  /// [Regs::with_reg] to also seed extra registers.
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::Regs;
+  /// let reg = Regs::new(0, 40).with_reg("base", 7);
+  /// assert_eq!((reg.acc, reg.dat, reg.get_reg("base")), (0, 40, 7));
+  /// ```
+  pub fn new(acc: i32, dat: i32) -> Regs {
+    Regs {
+      acc,
+      dat,
+      ..Regs::default()
+    }
+  }
+
+  /// Set the value of the named extra register, returning the registers for chaining.
+  pub fn with_reg(mut self, name: &'static str, value: i32) -> Regs {
+    self.set_reg(name, value);
+    self
+  }
+
+  /// Get the value of the named extra register. Registers that have never been set are 0.
+  pub fn get_reg(&self, name: &'static str) -> i32 {
+    self.extra.get(name).copied().unwrap_or(0)
+  }
+
+  /// Set the value of the named extra register.
+  pub fn set_reg(&mut self, name: &'static str, value: i32) {
+    self.extra.insert(Cow::Borrowed(name), value);
+  }
+
+  /// Add to acc, saturating to the game's range of [-999, 999] like the `add` instruction.
+  pub fn add(&mut self, value: i32) {
+    self.acc = clamp(self.acc.saturating_add(value));
+  }
+
+  /// Subtract from acc, saturating to the game's range of [-999, 999] like the `sub` instruction.
+  pub fn sub(&mut self, value: i32) {
+    self.acc = clamp(self.acc.saturating_sub(value));
+  }
+
+  /// Multiply acc, saturating to the game's range of [-999, 999] like the `mul` instruction.
+  pub fn mul(&mut self, value: i32) {
+    self.acc = clamp(self.acc.saturating_mul(value));
+  }
+
+  /// Like the `not` instruction: set acc to 100 if it's currently 0, or to 0 otherwise.
+  pub fn not(&mut self) {
+    self.acc = if self.acc == 0 { 100 } else { 0 };
+  }
+
+  /// Enable `+` instructions if `a` equals `b`, otherwise `-` instructions.
+  pub fn teq(&mut self, a: i32, b: i32) {
+    self.set_test(a == b);
+  }
+
+  /// Enable `+` instructions if `a` is greater than `b`, otherwise `-` instructions.
+  pub fn tgt(&mut self, a: i32, b: i32) {
+    self.set_test(a > b);
+  }
+
+  /// Enable `+` instructions if `a` is less than `b`, otherwise `-` instructions.
+  pub fn tlt(&mut self, a: i32, b: i32) {
+    self.set_test(a < b);
+  }
+
+  /// Enable `+` instructions if `a` is greater than `b`, `-` instructions if `a` is less than `b`,
+  /// and neither if they're equal.
+  pub fn tcp(&mut self, a: i32, b: i32) {
+    self.test = a.cmp(&b);
+  }
+
+  /// Whether `+` instructions would run, according to the last test instruction.
+  pub fn if_plus(&self) -> bool {
+    self.test == cmp::Ordering::Greater
+  }
+
+  /// Whether `-` instructions would run, according to the last test instruction.
+  pub fn if_minus(&self) -> bool {
+    self.test == cmp::Ordering::Less
+  }
+
+  fn set_test(&mut self, plus: bool) {
+    self.test = if plus {
+      cmp::Ordering::Greater
+    } else {
+      cmp::Ordering::Less
+    };
+  }
+
   /// Set the value of acc to the specified digit of the current value of acc. Index 0 is the ones
   /// digit, 1 is the tens digit, and 2 is the hundreds digit.
+  ///
+  /// As in the game, the digit is taken from the magnitude of acc, and keeps acc's sign.
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::Regs;
+  /// let mut reg = Regs::default();
+  /// for (index, expected) in [(0, -3), (1, -2), (2, -1), (3, 0)] {
+  ///   reg.acc = -123;
+  ///   reg.dgt(index);
+  ///   assert_eq!(reg.acc, expected);
+  /// }
+  ///
+  /// reg.acc = i32::MIN;
+  /// reg.dgt(0);
+  /// assert_eq!(reg.acc, -8);
+  /// ```
   pub fn dgt(&mut self, index: usize) {
-    self.acc = match index {
-      0 => self.acc % 10,
-      1 => (self.acc / 10) % 10,
-      2 => self.acc / 100,
-      _ => 0,
+    Self::dgt_on(&mut self.acc, index);
+  }
+
+  /// Like [Regs::dgt], but on any register, e.g. `dat`, instead of acc.
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::Regs;
+  /// let mut reg = Regs::default();
+  /// reg.dat = 456;
+  /// Regs::dgt_on(&mut reg.dat, 1);
+  /// assert_eq!(reg.dat, 5);
+  /// ```
+  pub fn dgt_on(target: &mut i32, index: usize) {
+    *target = match place_value(index) {
+      Some(place) => target.signum() * ((target.unsigned_abs() / place as u32) % 10) as i32,
+      None => 0,
     };
   }
 
   /// Set a single digit in the value of acc. If the given value is greater than 9, its ones digit
   /// is used. The index is specified in the same way as in the `dgt` macro.
+  ///
+  /// As in the game, the digit is set in the magnitude of acc, and acc keeps its sign, unless the
+  /// given value is negative, in which case acc becomes negative.
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::Regs;
+  /// let mut reg = Regs::default();
+  /// for (index, expected) in [(0, -125), (1, -153), (2, -523), (3, -123)] {
+  ///   reg.acc = -123;
+  ///   reg.dst(index, 5);
+  ///   assert_eq!(reg.acc, expected);
+  /// }
+  ///
+  /// reg.acc = 123;
+  /// reg.dst(0, -5);
+  /// assert_eq!(reg.acc, -125);
+  /// ```
   pub fn dst(&mut self, index: usize, value: i32) {
-    let digit = value % 10;
-    self.acc = match index {
-      0 => (self.acc / 10) * 10 + digit,
-      1 => (self.acc / 100) * 100 + (digit * 10) + self.acc % 10,
-      2 => (digit * 100) + (self.acc % 100),
-      _ => self.acc,
+    Self::dst_on(&mut self.acc, index, value);
+  }
+
+  /// Like [Regs::dst], but on any register, e.g. `dat`, instead of acc. A result too large for an
+  /// `i32`, which can only come from a target near `i32::MIN` or `i32::MAX`, saturates.
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::Regs;
+  /// let mut reg = Regs::default();
+  /// reg.acc = 7;
+  /// reg.dat = 456;
+  /// Regs::dst_on(&mut reg.dat, 2, reg.acc);
+  /// assert_eq!(reg.dat, 756);
+  ///
+  /// reg.dat = i32::MIN;
+  /// Regs::dst_on(&mut reg.dat, 0, 0);
+  /// assert_eq!(reg.dat, -2147483640);
+  /// Regs::dst_on(&mut reg.dat, 0, i32::MIN);
+  /// assert_eq!(reg.dat, -2147483648);
+  /// ```
+  pub fn dst_on(target: &mut i32, index: usize, value: i32) {
+    let place = match place_value(index) {
+      Some(place) => place,
+      None => return,
     };
+
+    // The magnitude of i32::MIN doesn't fit in an i32, so work with it as a u32.
+    let place = place as u32;
+    let magnitude = target.unsigned_abs();
+    let old_digit = (magnitude / place) % 10;
+    let new_digit = value.unsigned_abs() % 10;
+    let new_magnitude = i64::from(magnitude - old_digit * place + new_digit * place);
+
+    let result = if value < 0 || *target < 0 {
+      -new_magnitude
+    } else {
+      new_magnitude
+    };
+    *target = result.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+  }
+}
+
+/// The serialized form of [Regs], with the test flag as -1, 0, or 1.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Regs")]
+struct RegsData {
+  acc: i32,
+  dat: i32,
+  test: i8,
+  extra: HashMap<String, i32>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Regs {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    RegsData {
+      acc: self.acc,
+      dat: self.dat,
+      test: self.test as i8,
+      extra: self
+        .extra
+        .iter()
+        .map(|(name, value)| (name.to_string(), *value))
+        .collect(),
+    }
+    .serialize(serializer)
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Regs {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let data = RegsData::deserialize(deserializer)?;
+    Ok(Regs {
+      acc: data.acc,
+      dat: data.dat,
+      test: data.test.cmp(&0),
+      extra: data
+        .extra
+        .into_iter()
+        .map(|(name, value)| (Cow::Owned(name), value))
+        .collect(),
+    })
+  }
+}
+
+/// The place value of the digit at the given index, as used by `dgt` and `dst`.
+fn place_value(index: usize) -> Option<i32> {
+  match index {
+    0 => Some(1),
+    1 => Some(10),
+    2 => Some(100),
+    _ => None,
+  }
+}
+
+/// The error type for controller code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecError {
+  /// The scheduler is ending or resetting. Functions like `sleep` and `XBus::read` return this,
+  /// and controller code should propagate it out of `Controller::execute`.
+  Terminating,
+  /// The controller hit an error of its own, described by the message. A controller that returns
+  /// this stops running, and the message is reported by `Scheduler::faults` and in the scheduler's
+  /// `RunStats`.
+  Fault(String),
+}
+
+impl Error for ExecError {}
+
+impl std::fmt::Display for ExecError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Terminating => write!(f, "Scheduler is terminating"),
+      Self::Fault(message) => write!(f, "Controller fault: {}", message),
+    }
+  }
+}
+
+impl From<String> for ExecError {
+  fn from(message: String) -> Self {
+    Self::Fault(message)
   }
 }
 
@@ -46,32 +410,216 @@ impl Regs {
 /// `Arc<AtomicI32>`, so this will take care of itself.
 pub trait Controller {
   /// Returns the name of the controller. This is used to name the thread, and as a unique key for
-  /// when the thread is queueing in the scheduler.
+  /// when the thread is queueing in the scheduler. To build names at runtime, e.g. for a bank of
+  /// identical controllers, use [intern_name].
   fn name(&self) -> &'static str;
 
   /// The controller's code. The `acc` and `dat` registers are passed in as a struct. It should
   /// return `Ok(())` at the end, and propagate errors from any Result-returning function it calls
-  /// (i.e. `sleep`, `XBus::sleep`, `XBus::read`, and `XBus::write`).
+  /// (i.e. `sleep`, `XBus::sleep`, `XBus::read`, and `XBus::write`). To report a problem of its
+  /// own, it can return [ExecError::Fault].
   ///
   /// This function will be executed repeatedly until the Scheduler running the controller ends.
-  #[allow(clippy::result_unit_err)]
-  fn execute(&self, _: &mut Regs) -> Result<(), ()>;
+  /// Only going to sleep lets other controllers run, so a controller whose executions keep
+  /// returning without sleeping would loop forever; after a number of them in a row (see
+  /// [Scheduler::set_max_executions_without_sleep]), it faults instead. Reads and writes that
+  /// complete without waiting, e.g. from an input source with values queued, don't count as
+  /// sleeping. An execution that only consumes a queued value is fine as long as a later one sleeps
+  /// once the queue is empty.
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::{inputsource, outputsink};
+  /// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+  /// # use shenzhen_vm::scheduler::Scheduler;
+  /// # use shenzhen_vm::xbus::XBus;
+  /// struct Idle;
+  /// impl Controller for Idle {
+  ///   fn name(&self) -> &'static str { "idle" }
+  ///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+  ///     Ok(())
+  ///   }
+  /// }
+  ///
+  /// // Never waits, since the input always has a value and the sink always accepts one.
+  /// struct Copier(XBus, XBus);
+  /// impl Controller for Copier {
+  ///   fn name(&self) -> &'static str { "copier" }
+  ///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+  ///     self.1.write(self.0.read()?)
+  ///   }
+  /// }
+  ///
+  /// let (_, input) = inputsource::from_iter(false, [1]);
+  /// let (_, output) = outputsink::new("out", false);
+  /// let mut scheduler = Scheduler::new(vec![Box::new(Idle), Box::new(Copier(input, output))]);
+  /// scheduler.advance().unwrap();
+  /// let faults = scheduler.faults();
+  /// let expected = "completed 1000 executions in a row without sleeping";
+  /// assert_eq!(faults["idle"], format!("controller idle {}", expected));
+  /// assert_eq!(faults["copier"], format!("controller copier {}", expected));
+  /// # scheduler.end();
+  /// ```
+  fn execute(&self, _: &mut Regs) -> Result<(), ExecError>;
+
+  /// The registers the controller starts with, before its first `execute`. The default is all
+  /// zeros; override this to seed registers, e.g. a base address in `dat`, without spending a
+  /// timestep setting them up.
+  fn initial_regs(&self) -> Regs {
+    Regs::default()
+  }
+
+  /// One-time setup, run once in the first timestep before `execute` runs for the first time,
+  /// e.g. to prime a bus. Errors should be propagated like in `execute`. The default does nothing.
+  fn init(&self, _: &mut Regs) -> Result<(), ExecError> {
+    Ok(())
+  }
+
+  /// Called once on the controller's thread when the scheduler ends, after `execute` has returned
+  /// its error, e.g. to flush resources the controller owns. This runs even if the controller was
+  /// blocked on an XBus when the scheduler ended, as long as `execute` propagated the error. The
+  /// default does nothing.
+  fn shutdown(&self) {}
+}
+
+/// Names passed to [intern_name] so far.
+static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+/// Get a `&'static str` with the same contents as `name`, for [Controller::name] and the other
+/// places that need one, when the name is built at runtime.
+///
+/// This works around names being `&'static str` by leaking: each distinct name is allocated once
+/// and never freed. The leak is bounded by the number of distinct names, so interning the same
+/// names repeatedly, e.g. for every run of a design, doesn't use more memory, but names taken from
+/// untrusted input shouldn't be interned.
+///
+/// ```
+/// # use shenzhen_vm::controller::intern_name;
+/// let names: Vec<&'static str> = (0..3).map(|i| intern_name(&format!("motor-{}", i))).collect();
+/// assert_eq!(names, ["motor-0", "motor-1", "motor-2"]);
+/// assert!(std::ptr::eq(names[1], intern_name("motor-1")));
+/// ```
+pub fn intern_name(name: &str) -> &'static str {
+  let mut names = NAMES.lock().unwrap();
+  match names.get(name) {
+    Some(interned) => interned,
+    None => {
+      let interned: &'static str = Box::leak(name.into());
+      names.insert(interned);
+      interned
+    }
+  }
 }
 
 thread_local! {
   /// The name of the current controller
-  static CONTROLLER_NAME: RefCell<&'static str> = RefCell::new("");
+  static CONTROLLER_NAME: RefCell<&'static str> = const { RefCell::new("") };
 
   /// The sending half of a channel that the current controller should use to communicate with the
   /// scheduler.
-  static SENDER: RefCell<MaybeUninit<Sender<SleepMessage>>> = RefCell::new(MaybeUninit::uninit());
+  static SENDER: RefCell<MaybeUninit<Sender<SleepMessage>>> =
+    const { RefCell::new(MaybeUninit::uninit()) };
+
+  /// The scheduler's current timestep number, shared by all its controllers.
+  static CLOCK: RefCell<Option<Arc<AtomicU32>>> = const { RefCell::new(None) };
+
+  /// The current controller's power usage counter, which the scheduler also holds.
+  static POWER: RefCell<Option<Arc<AtomicU64>>> = const { RefCell::new(None) };
+
+  /// A fault raised by a component during the current controller's XBus operation, to be returned
+  /// from that operation.
+  static PENDING_FAULT: RefCell<Option<String>> = const { RefCell::new(None) };
+
+  /// The number of times the current controller has gone to sleep, i.e. sent a sleep message to
+  /// the scheduler. Operations that complete without waiting don't count.
+  static SLEEPS: Cell<u64> = const { Cell::new(0) };
+
+  /// Whether the scheduler has told the current controller to start over, rather than terminate.
+  static RESETTING: Cell<bool> = const { Cell::new(false) };
+
+  /// The scheduler's trace buffer, shared by all its controllers.
+  static TRACER: RefCell<Option<Arc<Tracer>>> = const { RefCell::new(None) };
+
+  /// Every XBus the scheduler's controllers have used, shared by all of them.
+  static BUSES: RefCell<Option<SharedBuses>> = const { RefCell::new(None) };
 }
 
 pub(crate) fn current_name() -> &'static str {
   CONTROLLER_NAME.with(|cell| *cell.borrow())
 }
 
+pub(crate) fn current_time() -> u32 {
+  CLOCK.with(|cell| {
+    cell
+      .borrow()
+      .as_ref()
+      .map_or(0, |clock| clock.load(Ordering::Relaxed))
+  })
+}
+
+/// Make [current_time] on this thread read the given clock. Controller threads get their
+/// scheduler's clock when they start; the scheduler also calls this on its own thread, so that
+/// sources polled there while it checks which controllers can run see the right time.
+pub(crate) fn set_clock(clock: Arc<AtomicU32>) {
+  CLOCK.with(|cell| *cell.borrow_mut() = Some(clock));
+}
+
+/// Charge the current controller for one operation (a sleep or an XBus operation).
+pub(crate) fn use_power() {
+  POWER.with(|cell| {
+    if let Some(power) = cell.borrow().as_ref() {
+      power.fetch_add(1, Ordering::Relaxed);
+    }
+  })
+}
+
+/// Record an operation by the current controller, if tracing is enabled.
+pub(crate) fn trace(op: TraceOp) {
+  TRACER.with(|cell| {
+    if let Some(tracer) = cell.borrow().as_ref() {
+      if tracer.is_enabled() {
+        tracer.record(TraceEvent {
+          time: current_time(),
+          controller: current_name(),
+          op,
+        });
+      }
+    }
+  })
+}
+
+/// For components: make the XBus operation the current controller is in the middle of return
+/// [ExecError::Fault] with the given message.
+pub(crate) fn raise_fault(message: String) {
+  PENDING_FAULT.with(|cell| *cell.borrow_mut() = Some(message));
+}
+
+/// Return the fault raised by a component, if any, clearing it.
+pub(crate) fn take_fault() -> Result<(), ExecError> {
+  match PENDING_FAULT.with(|cell| cell.borrow_mut().take()) {
+    Some(message) => Err(ExecError::Fault(message)),
+    None => Ok(()),
+  }
+}
+
+pub(crate) fn set_resetting() {
+  RESETTING.with(|cell| cell.set(true));
+}
+
+/// Tell the current controller's scheduler about a bus the controller uses, so that
+/// [Scheduler::reset] can clear it.
+pub(crate) fn register_bus(bus: &XBus) {
+  BUSES.with(|cell| {
+    if let Some(buses) = cell.borrow().as_ref() {
+      let mut buses = buses.lock().unwrap();
+      if !buses.iter().any(|b| b.same_bus(bus)) {
+        buses.push(bus.clone());
+      }
+    }
+  })
+}
+
 pub(crate) fn send_to_scheduler(message: SleepMessage) {
+  SLEEPS.with(|cell| cell.set(cell.get() + 1));
   SENDER.with(|cell| {
     unsafe { cell.borrow().assume_init_ref() }
       .send(message)
@@ -79,25 +627,92 @@ pub(crate) fn send_to_scheduler(message: SleepMessage) {
   })
 }
 
+/// The state a controller thread shares with its scheduler.
+pub(crate) struct ControllerContext {
+  pub(crate) sender: Sender<SleepMessage>,
+  pub(crate) clock: Arc<AtomicU32>,
+  pub(crate) power: Arc<AtomicU64>,
+  pub(crate) tracer: Arc<Tracer>,
+  pub(crate) regs: SharedRegs,
+  pub(crate) buses: SharedBuses,
+  /// The limit on executions in a row without sleeping.
+  pub(crate) max_unslept: Arc<AtomicU32>,
+}
+
 pub(crate) fn start(
   ctrl: Box<dyn Controller + Send>,
-  sender: Sender<SleepMessage>,
+  context: ControllerContext,
 ) -> thread::JoinHandle<()> {
   thread::Builder::new()
     .name(ctrl.name().into())
     .spawn(move || {
+      let ControllerContext {
+        sender,
+        clock,
+        power,
+        tracer,
+        regs,
+        buses,
+        max_unslept,
+      } = context;
+
       // Set up thread-local state
       CONTROLLER_NAME.with(|cell| *cell.borrow_mut() = ctrl.name());
       SENDER.with(|cell| {
         cell.borrow_mut().write(sender);
       });
+      CLOCK.with(|cell| *cell.borrow_mut() = Some(clock));
+      POWER.with(|cell| *cell.borrow_mut() = Some(power));
+      TRACER.with(|cell| *cell.borrow_mut() = Some(tracer));
+      BUSES.with(|cell| *cell.borrow_mut() = Some(buses));
+
+      let name = ctrl.name();
+      let publish = |state: &Regs| {
+        regs.lock().unwrap().insert(name, (state.acc, state.dat));
+      };
 
-      // Don't start executing the body until the first advance() call
-      Scheduler::sleep(SleepToken::Time(0)).unwrap();
+      // Each iteration starts the controller from scratch; it only repeats if the scheduler is
+      // reset.
+      loop {
+        let mut state = ctrl.initial_regs();
+        publish(&state);
 
-      let mut state = Regs { acc: 0, dat: 0 };
+        let mut run = || -> Result<(), ExecError> {
+          // Don't start executing the body until the first advance() call. The scheduler may
+          // also be ended before that.
+          Scheduler::sleep(SleepToken::Time(0))?;
+          ctrl.init(&mut state)?;
+          publish(&state);
+          let mut unslept = 0;
+          loop {
+            let before = SLEEPS.with(Cell::get);
+            ctrl.execute(&mut state)?;
+            publish(&state);
+            if SLEEPS.with(Cell::get) != before {
+              unslept = 0;
+              continue;
+            }
+            unslept += 1;
+            if unslept >= max_unslept.load(Ordering::Relaxed) {
+              return Err(ExecError::Fault(format!(
+                "controller {} completed {} executions in a row without sleeping",
+                name, unslept
+              )));
+            }
+          }
+        };
 
-      while ctrl.execute(&mut state).is_ok() {}
+        if let Err(ExecError::Fault(message)) = run() {
+          // Stay parked, with the fault visible to the scheduler, until it ends or resets.
+          let _ = Scheduler::sleep(SleepToken::Faulted(message));
+        }
+
+        if !RESETTING.with(|cell| cell.replace(false)) {
+          break;
+        }
+      }
+
+      ctrl.shutdown();
     })
     .unwrap()
 }
@@ -121,6 +736,22 @@ macro_rules! gen {
   };
 }
 
+/// Like [gen], but with no off phase: it sets the given simple input to 100 for `on_steps`
+/// timesteps, and then leaves it at 100 rather than resetting it to 0.
+///
+/// NB: `gen!(pin, n, 0)` still ends with the pin at 0, so the pulse ends as soon as the macro
+/// returns. Use this macro instead when the pin should stay high until something else explicitly
+/// changes it.
+#[macro_export]
+macro_rules! gen_hold {
+  ($pin:expr, $on_steps:expr) => {
+    $pin.store(100, Ordering::Relaxed);
+    if $on_steps > 0 {
+      sleep($on_steps)?;
+    }
+  };
+}
+
 /// A convenience macro for reading from an `AtomicI32` (inside an `Arc` or not).
 #[macro_export]
 macro_rules! rd {
@@ -128,3 +759,41 @@ macro_rules! rd {
     $arc_atomic.load(Ordering::Relaxed)
   };
 }
+
+/// Mimics a block of `+` instructions in the game: runs the body only if the last test
+/// instruction on `reg` (`teq`, `tgt`, `tlt`, or `tcp`) enabled `+` instructions.
+///
+/// The flag is checked once, when the block starts. A test inside the body affects the `plus!` and
+/// `minus!` blocks that follow, but not the rest of the body it's in. The game checks the flag on
+/// every line, so to match it, a test inside a conditional block should be the block's last
+/// statement.
+///
+/// ```
+/// # use shenzhen_vm::controller::Regs;
+/// # use shenzhen_vm::{minus, plus};
+/// let mut reg = Regs::default();
+/// reg.acc = 5;
+/// reg.tgt(reg.acc, 3);
+/// plus!(reg, {
+///   reg.acc = 100;
+/// });
+/// minus!(reg, {
+///   reg.acc = -100;
+/// });
+/// assert_eq!(reg.acc, 100);
+/// ```
+#[macro_export]
+macro_rules! plus {
+  ($reg:expr, $body:block) => {
+    if $reg.if_plus() $body
+  };
+}
+
+/// Mimics a block of `-` instructions in the game: runs the body only if the last test
+/// instruction on `reg` enabled `-` instructions. See [plus] for how the flag is checked.
+#[macro_export]
+macro_rules! minus {
+  ($reg:expr, $body:block) => {
+    if $reg.if_minus() $body
+  };
+}
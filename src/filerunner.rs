@@ -2,12 +2,13 @@
 
 use std::collections::HashMap;
 use std::error::Error;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::num::ParseIntError;
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 
 use crate::components::inputsource::InputSource;
-use crate::components::outputsink::OutputSink;
+use crate::components::outputsink::{OutputSink, WriteLog};
 use crate::scheduler::Scheduler;
 
 /// Represents a bus used as input, either a simple I/O pin or an [InputSource].
@@ -23,9 +24,29 @@ pub enum OutputBus<'a> {
 }
 
 pub struct FileRunner<'a> {
-  reader: BufReader<&'a mut dyn Read>,
+  source: RowSource<'a>,
+  steps_per_row: u32,
+  prime_row: bool,
   inputs: Vec<(usize, String)>,
-  outputs: Vec<(usize, String)>,
+  outputs: Vec<(usize, String, OutputFormat)>,
+  /// The index of the `order` field, if there is one.
+  order: Option<usize>,
+  write_log: Option<Arc<WriteLog>>,
+  /// The number of fields in the header, which every data row must match.
+  field_count: usize,
+  /// The number of data rows read so far.
+  rows_read: usize,
+}
+
+/// Where a [FileRunner] gets its rows of data from.
+enum RowSource<'a> {
+  /// CSV text, parsed as RFC 4180 if `quoted` is true.
+  Text {
+    reader: BufReader<&'a mut dyn Read>,
+    quoted: bool,
+  },
+  /// Rows that are already split into fields.
+  Rows(Box<dyn Iterator<Item = Vec<String>> + 'a>),
 }
 
 #[derive(Debug)]
@@ -50,12 +71,272 @@ impl std::fmt::Display for VerifyError {
   }
 }
 
+/// How an output's values are printed in mismatch messages and recorded data, given in the header
+/// after the output's name, like `out display:%03d`. The spec is a `%`, optionally `+` to always
+/// show the sign, optionally `0` to pad with zeros instead of spaces, a minimum width, and `d`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputFormat {
+  pub width: usize,
+  pub zero_pad: bool,
+  pub signed: bool,
+}
+
+impl OutputFormat {
+  /// Parse a spec like `%03d`, or return `None` if it's malformed.
+  fn parse(spec: &str) -> Option<OutputFormat> {
+    let mut rest = spec.strip_prefix('%')?.strip_suffix('d')?;
+    let mut format = OutputFormat::default();
+    if let Some(r) = rest.strip_prefix('+') {
+      format.signed = true;
+      rest = r;
+    }
+    if let Some(r) = rest.strip_prefix('0') {
+      format.zero_pad = true;
+      rest = r;
+    }
+    if !rest.is_empty() {
+      format.width = rest.parse().ok()?;
+    }
+    Some(format)
+  }
+
+  /// Format a single value.
+  pub fn format(&self, value: i32) -> String {
+    let width = self.width;
+    match (self.signed, self.zero_pad) {
+      (false, false) => format!("{:width$}", value),
+      (false, true) => format!("{:0width$}", value),
+      (true, false) => format!("{:+width$}", value),
+      (true, true) => format!("{:+0width$}", value),
+    }
+  }
+
+  /// Format a list of values, separated by `separator`.
+  fn format_all(&self, values: &[i32], separator: &str) -> String {
+    values
+      .iter()
+      .map(|v| self.format(*v))
+      .collect::<Vec<String>>()
+      .join(separator)
+  }
+
+  /// The spec to write after an output's name in a header, including the `:`.
+  fn header_suffix(&self) -> String {
+    if *self == OutputFormat::default() {
+      return String::new();
+    }
+    format!(
+      ":%{}{}{}d",
+      if self.signed { "+" } else { "" },
+      if self.zero_pad { "0" } else { "" },
+      if self.width > 0 {
+        self.width.to_string()
+      } else {
+        String::new()
+      }
+    )
+  }
+}
+
+/// An output that didn't match the expected values in one timestep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+  pub timestep: usize,
+  pub name: String,
+  pub expected: Vec<i32>,
+  pub actual: Vec<i32>,
+  /// The output's format from the header, used when displaying the values.
+  pub format: OutputFormat,
+}
+
+impl std::fmt::Display for Mismatch {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.expected.len() != self.actual.len() {
+      write!(
+        f,
+        "Incorrect number of values output for '{}' at timestep {}: expected {}, got {}",
+        self.name,
+        self.timestep,
+        self.expected.len(),
+        self.actual.len()
+      )
+    } else if self.expected.len() == 1 {
+      write!(
+        f,
+        "Incorrect output '{}' at time {}: expected {}, got {}",
+        self.name,
+        self.timestep,
+        self.format.format(self.expected[0]),
+        self.format.format(self.actual[0])
+      )
+    } else {
+      write!(
+        f,
+        "Incorrect output '{}' at time {}: expected [{}], got [{}]",
+        self.name,
+        self.timestep,
+        self.format.format_all(&self.expected, ", "),
+        self.format.format_all(&self.actual, ", ")
+      )
+    }
+  }
+}
+
+/// Writes in one timestep that didn't happen in the expected order, according to the `order`
+/// field of the data. Each write is a (sink name, value) pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderMismatch {
+  pub timestep: usize,
+  pub expected: Vec<(String, i32)>,
+  pub actual: Vec<(String, i32)>,
+}
+
+impl std::fmt::Display for OrderMismatch {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let join = |events: &[(String, i32)]| {
+      events
+        .iter()
+        .map(|(name, value)| format!("{}:{}", name, value))
+        .collect::<Vec<String>>()
+        .join(", ")
+    };
+    write!(
+      f,
+      "Incorrect order of writes at time {}: expected [{}], got [{}]",
+      self.timestep,
+      join(&self.expected),
+      join(&self.actual)
+    )
+  }
+}
+
+/// The result of [FileRunner::verify_report].
+#[derive(Debug)]
+pub struct VerifyReport {
+  /// The number of timesteps run.
+  pub timesteps: usize,
+  /// Every output that didn't match, in order.
+  pub mismatches: Vec<Mismatch>,
+  /// Every timestep whose writes didn't happen in the expected order, if the data has an `order`
+  /// field.
+  pub order_mismatches: Vec<OrderMismatch>,
+  /// Whether every output matched.
+  pub passed: bool,
+}
+
+/// Lines starting with this (after trimming) are ignored.
+const COMMENT: char = '#';
+
+/// An output field that accepts any output.
+const WILDCARD: &str = "*";
+
+/// Read the next row of CSV text, split into fields with surrounding whitespace trimmed, and
+/// skipping comments. Blank lines are skipped too, unless `keep_blank` is true, in which case
+/// they're rows of one blank field. Returns `None` at the end of the text.
+fn read_text_row(
+  reader: &mut BufReader<&mut dyn Read>,
+  quoted: bool,
+  keep_blank: bool,
+) -> Result<Option<Vec<String>>, std::io::Error> {
+  let mut line = String::new();
+  loop {
+    line.clear();
+    if reader.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+
+    let trimmed = line.trim();
+    if trimmed.is_empty() && keep_blank {
+      return Ok(Some(vec![String::new()]));
+    }
+    if !trimmed.is_empty() && !trimmed.starts_with(COMMENT) {
+      break;
+    }
+  }
+
+  if !quoted {
+    return Ok(Some(line.split(',').map(|s| s.trim().into()).collect()));
+  }
+
+  let mut fields = vec![];
+  let mut field = String::new();
+  let mut in_quotes = false;
+
+  loop {
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+      match c {
+        '"' if in_quotes && chars.peek() == Some(&'"') => {
+          field.push('"');
+          chars.next();
+        }
+        '"' => in_quotes = !in_quotes,
+        ',' if !in_quotes => fields.push(std::mem::take(&mut field).trim().into()),
+        _ => field.push(c),
+      }
+    }
+
+    if !in_quotes {
+      break;
+    }
+
+    // A quoted field continues onto the next line.
+    line.clear();
+    if reader.read_line(&mut line)? == 0 {
+      return Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "Unterminated quoted field",
+      ));
+    }
+  }
+
+  fields.push(field.trim().into());
+  Ok(Some(fields))
+}
+
+/// Parse a number from the data: decimal, or hex or binary with a `0x` or `0b` prefix, optionally
+/// preceded by a minus sign.
+fn parse_value(text: &str) -> Result<i32, ParseIntError> {
+  let (negative, unsigned) = match text.strip_prefix('-') {
+    Some(rest) => (true, rest),
+    None => (false, text),
+  };
+
+  let (radix, digits) = if let Some(hex) = unsigned.strip_prefix("0x") {
+    (16, hex)
+  } else if let Some(bin) = unsigned.strip_prefix("0b") {
+    (2, bin)
+  } else {
+    return text.parse();
+  };
+
+  // from_str_radix accepts a sign, but it must come before the prefix.
+  if digits.starts_with(['+', '-']) {
+    return text.parse();
+  }
+
+  // Parse with the sign attached so that i32::MIN is representable.
+  if negative {
+    i32::from_str_radix(&format!("-{}", digits), radix)
+  } else {
+    i32::from_str_radix(digits, radix)
+  }
+}
+
 macro_rules! error {
   ($fmt:literal, $( $arg:expr ),*) => {
     Err(VerifyError(format!($fmt, $( $arg ),*)).into())
   }
 }
 
+/// Parse a write in the `order` field, like `display:5`, into the sink name and the value.
+fn parse_event(text: &str) -> Result<(String, i32), Box<dyn Error>> {
+  match text.rsplit_once(':') {
+    Some((name, value)) if !name.is_empty() => Ok((String::from(name), parse_value(value)?)),
+    _ => error!("Invalid write in order field: '{}'", text),
+  }
+}
+
 impl<'a> FileRunner<'a> {
   /// Create a new FileRunner, passing in a [Read] object containing CSV data of inputs and
   /// expected outputs.
@@ -69,25 +350,166 @@ impl<'a> FileRunner<'a> {
   /// values per timestep, separate them with spaces. If an input field is blank, that input will
   /// be unchanged in that timestep (simple left as-is, nothing added to XBus). If a simple output
   /// field is blank, it will not be checked in that timestep. If an XBus output field is blank,
-  /// FileRunner will check that there was no output on that bus in that timestep.
+  /// FileRunner will check that there was no output on that bus in that timestep. An output field
+  /// of `*` accepts anything: any value for a simple output, and any number of values for an XBus
+  /// output.
+  ///
+  /// Values may be written in decimal, or in hex or binary with a `0x` or `0b` prefix (e.g. `0x1F`,
+  /// `-0b101`).
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::{inputsource, outputsink};
+  /// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+  /// # use shenzhen_vm::filerunner::{FileRunner, InputBus, OutputBus};
+  /// # use shenzhen_vm::scheduler::{sleep, Scheduler};
+  /// # use shenzhen_vm::xbus::XBus;
+  /// # use std::collections::HashMap;
+  /// # struct Doubler(XBus, XBus);
+  /// # impl Controller for Doubler {
+  /// #   fn name(&self) -> &'static str { "doubler" }
+  /// #   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+  /// #     self.0.sleep()?;
+  /// #     self.1.write(self.0.read()? * 2)?;
+  /// #     sleep(1)
+  /// #   }
+  /// # }
+  /// let run = |csv: &str| {
+  ///   let (source, input) = inputsource::blocking();
+  ///   let (sink, output) = outputsink::new("y", false);
+  ///   let mut scheduler = Scheduler::new(vec![Box::new(Doubler(input, output))]);
+  ///   let mut data = csv.as_bytes();
+  ///   let result = FileRunner::new(&mut data).unwrap().verify(
+  ///     &mut scheduler,
+  ///     HashMap::from([("x", InputBus::XBus(&source))]),
+  ///     HashMap::from([("y", OutputBus::XBus(&sink))]),
+  ///   );
+  ///   scheduler.end();
+  ///   result.map_err(|e| e.to_string())
+  /// };
+  ///
+  /// assert_eq!(run("in x,out y\n0x1F,62\n-0x1F,-0b111110\n0b101,0xA\n-7,-14\n"), Ok(4));
+  /// assert_eq!(run("in x,out y\n0xZZ,0\n"), Err("invalid digit found in string".into()));
+  /// ```
+  ///
+  /// Lines starting with `#` are ignored, so the data can be annotated, and so are blank lines,
+  /// except when the header has only one field. Then a blank line is a data row with that field
+  /// blank, as in files written before comments were supported.
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::{inputsource, outputsink};
+  /// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+  /// # use shenzhen_vm::filerunner::{FileRunner, InputBus, OutputBus};
+  /// # use shenzhen_vm::scheduler::{sleep, Scheduler};
+  /// # use shenzhen_vm::xbus::XBus;
+  /// # use std::collections::HashMap;
+  /// struct Doubler(XBus, XBus);
+  /// impl Controller for Doubler {
+  ///   fn name(&self) -> &'static str { "doubler" }
+  ///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+  ///     self.0.sleep()?;
+  ///     self.1.write(self.0.read()? * 2)?;
+  ///     sleep(1)
+  ///   }
+  /// }
+  ///
+  /// let csv = "## Doubles its input.
+  /// in x,out y
+  /// 1,2
+  ///
+  ///   ## Nothing in, nothing out.
+  /// ,
+  /// 3,6
+  /// ## The end.
+  /// ";
+  /// let (source, input) = inputsource::blocking();
+  /// let (sink, output) = outputsink::new("y", false);
+  /// let mut scheduler = Scheduler::new(vec![Box::new(Doubler(input, output))]);
+  /// let mut data = csv.as_bytes();
+  /// let timesteps = FileRunner::new(&mut data)
+  ///   .unwrap()
+  ///   .verify(
+  ///     &mut scheduler,
+  ///     HashMap::from([("x", InputBus::XBus(&source))]),
+  ///     HashMap::from([("y", OutputBus::XBus(&sink))]),
+  ///   )
+  ///   .unwrap();
+  /// assert_eq!(timesteps, 3);
+  /// # scheduler.end();
+  /// ```
+  ///
+  /// An output's header field may end with a format, like `out display:%03d`, controlling how its
+  /// values are printed in error messages and by [FileRunner::record]; see [OutputFormat].
+  ///
+  /// A header field of `order` checks the order of writes across XBus outputs; see
+  /// [FileRunner::with_write_log].
   ///
   /// NB: this is not parsed as real CSV; in particular, there is no quoting. Since that the only
-  /// possible data is integers, there should be no need for quoting.
-  pub fn new(in_stream: &'a mut dyn Read) -> Result<FileRunner, std::io::Error> {
-    let mut reader = BufReader::new(in_stream);
+  /// possible data is integers, there should be no need for quoting. Use
+  /// [FileRunner::new_rfc4180] if you need it.
+  pub fn new(in_stream: &'a mut dyn Read) -> Result<FileRunner<'a>, std::io::Error> {
+    Self::make(RowSource::Text {
+      reader: BufReader::new(in_stream),
+      quoted: false,
+    })
+  }
 
-    let mut header = String::new();
-    reader.read_line(&mut header)?;
+  /// Create a new FileRunner like [FileRunner::new], but parse the data as real CSV, as described
+  /// by RFC 4180: fields may be quoted with `"`, in which case they may contain commas, newlines,
+  /// and quotes (written as `""`). Inside a field, multiple values are still separated by spaces.
+  pub fn new_rfc4180(in_stream: &'a mut dyn Read) -> Result<FileRunner<'a>, std::io::Error> {
+    Self::make(RowSource::Text {
+      reader: BufReader::new(in_stream),
+      quoted: true,
+    })
+  }
 
-    let field_specs = header.split(',').map(|s| s.trim());
-    let mut inputs = vec![];
-    let mut outputs = vec![];
+  /// Create a new FileRunner from rows that are already split into fields, rather than CSV text.
+  /// The header and the fields of each row have the same meaning as in [FileRunner::new], except
+  /// that there are no comments.
+  pub fn from_rows(
+    header: Vec<String>,
+    rows: impl Iterator<Item = Vec<String>> + 'a,
+  ) -> Result<FileRunner<'a>, std::io::Error> {
+    Self::make(RowSource::Rows(Box::new(
+      std::iter::once(header).chain(rows),
+    )))
+  }
+
+  fn make(source: RowSource<'a>) -> Result<FileRunner<'a>, std::io::Error> {
+    let mut runner = FileRunner {
+      source,
+      steps_per_row: 1,
+      prime_row: false,
+      inputs: vec![],
+      outputs: vec![],
+      order: None,
+      write_log: None,
+      field_count: 0,
+      rows_read: 0,
+    };
+
+    let field_specs = runner.read_row()?.unwrap_or_default();
+    runner.field_count = field_specs.len();
 
     for (index, field_spec) in field_specs.into_iter().enumerate() {
       if let Some(name) = field_spec.strip_prefix("in ") {
-        inputs.push((index, String::from(name)));
-      } else if let Some(name) = field_spec.strip_prefix("out ") {
-        outputs.push((index, String::from(name)));
+        runner.inputs.push((index, String::from(name)));
+      } else if let Some(name_spec) = field_spec.strip_prefix("out ") {
+        let (name, format) = match name_spec.split_once(':') {
+          None => (name_spec, OutputFormat::default()),
+          Some((name, spec)) => match OutputFormat::parse(spec.trim()) {
+            Some(format) => (name.trim_end(), format),
+            None => {
+              return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid output format in header: {}", field_spec),
+              ));
+            }
+          },
+        };
+        runner.outputs.push((index, String::from(name), format));
+      } else if field_spec == "order" {
+        runner.order = Some(index);
       } else {
         return Err(std::io::Error::new(
           std::io::ErrorKind::InvalidData,
@@ -96,11 +518,90 @@ impl<'a> FileRunner<'a> {
       }
     }
 
-    Ok(FileRunner {
-      reader,
-      inputs,
-      outputs,
-    })
+    Ok(runner)
+  }
+
+  /// Make each data row represent `steps_per_row` timesteps instead of one. Inputs are set before
+  /// the first of those timesteps, and outputs are checked after the last. Simple outputs are
+  /// checked only at that point; XBus outputs must match all the values written during all the
+  /// row's timesteps, in order. Timestep numbers returned from verification and shown in errors
+  /// then count rows, not scheduler timesteps. Panics if `steps_per_row` is 0.
+  pub fn with_step_ratio(mut self, steps_per_row: u32) -> Self {
+    assert!(steps_per_row > 0, "Each row needs at least one timestep");
+    self.steps_per_row = steps_per_row;
+    self
+  }
+
+  /// Check the order of writes across output sinks, using `log`, which the sinks must be attached
+  /// to with [OutputSink::log_to]. The data must then have an `order` field, in which each row
+  /// lists the writes expected during that row, in order, separated by spaces, as the sink's name
+  /// (the one it was created with), a `:`, and the value, like `a:1 b:2 a:3`. A blank field expects
+  /// no writes, and `*` accepts anything.
+  ///
+  /// This is in addition to the checks of each output's own values.
+  pub fn with_write_log(mut self, log: Arc<WriteLog>) -> Self {
+    self.write_log = Some(log);
+    self
+  }
+
+  /// Treat the first data row as a priming row: its inputs are set before the scheduler is
+  /// advanced for the first time, without advancing it or checking outputs, and then the next row
+  /// is handled as usual. Use this to preload an XBus input that a controller should find already
+  /// waiting, in addition to the values given for the first timestep. The priming row's output
+  /// fields are ignored, and it isn't counted as a timestep.
+  pub fn with_prime_row(mut self) -> Self {
+    self.prime_row = true;
+    self
+  }
+
+  /// If this runner has a priming row, read it and set its inputs, returning the row.
+  fn prime(
+    &mut self,
+    inputs: &HashMap<&str, InputBus<'_>>,
+  ) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+    if !self.prime_row {
+      return Ok(None);
+    }
+    let row = self.read_data_row()?;
+    if let Some(split_line) = &row {
+      self.set_inputs(split_line, inputs)?;
+    }
+    Ok(row)
+  }
+
+  /// Read the next row of data, split into fields with surrounding whitespace trimmed. Returns
+  /// `None` at the end of the data.
+  fn read_row(&mut self) -> Result<Option<Vec<String>>, std::io::Error> {
+    // With only one field, a blank line is a row with that field blank, as it always has been.
+    let keep_blank = self.field_count == 1;
+    match &mut self.source {
+      RowSource::Text { reader, quoted } => read_text_row(reader, *quoted, keep_blank),
+      RowSource::Rows(rows) => Ok(
+        rows
+          .next()
+          .map(|row| row.iter().map(|s| s.trim().into()).collect()),
+      ),
+    }
+  }
+
+  /// Read the next data row like [FileRunner::read_row], checking that it has as many fields as
+  /// the header.
+  fn read_data_row(&mut self) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+    let row = match self.read_row()? {
+      Some(row) => row,
+      None => return Ok(None),
+    };
+    self.rows_read += 1;
+
+    if row.len() != self.field_count {
+      return error!(
+        "Data row {} has {} fields, but the header has {}",
+        self.rows_read,
+        row.len(),
+        self.field_count
+      );
+    }
+    Ok(Some(row))
   }
 
   /// Run the given [Scheduler], verifying actual output against expected.
@@ -111,9 +612,12 @@ impl<'a> FileRunner<'a> {
   ///
   /// Errors if:
   /// - There are unparseable numbers in the data
+  /// - A data row has a different number of fields than the header
   /// - An input/output name in the data is missing from the given HashMaps
+  /// - A key in the given HashMaps isn't an input/output name in the data
   /// - Multiple values are given for a simple input or output
   /// - An output doesn't match
+  /// - The scheduler deadlocks
   ///
   /// Returns the number of timesteps verified.
   pub fn verify(
@@ -122,107 +626,332 @@ impl<'a> FileRunner<'a> {
     inputs: HashMap<&str, InputBus<'_>>,
     outputs: HashMap<&str, OutputBus<'_>>,
   ) -> Result<usize, Box<dyn Error>> {
-    let mut timestep_number = 0;
-    let mut buffer = String::new();
-
-    while {
-      buffer.clear();
-      self
-        .reader
-        .read_line(&mut buffer)
-        .map_or(false, |sz| sz > 0)
-    } {
-      let split_line: Vec<&str> = buffer.split(',').map(|s| s.trim()).collect();
-
-      for (index, name) in self.inputs.iter() {
-        let value_from_file = split_line[*index];
-        if value_from_file.is_empty() {
+    self.verify_with_tolerance(scheduler, inputs, outputs, 0)
+  }
+
+  /// Run the given [Scheduler] like [FileRunner::verify], but consider an output value to match if
+  /// it's within `tol` of the expected value. Error messages still show the exact values.
+  pub fn verify_with_tolerance(
+    &mut self,
+    scheduler: &mut Scheduler,
+    inputs: HashMap<&str, InputBus<'_>>,
+    outputs: HashMap<&str, OutputBus<'_>>,
+    tol: u32,
+  ) -> Result<usize, Box<dyn Error>> {
+    let report = self.run(scheduler, &inputs, &outputs, None, tol, true)?;
+    if let Some(mismatch) = report.mismatches.first() {
+      return Err(VerifyError(mismatch.to_string()).into());
+    }
+    match report.order_mismatches.first() {
+      Some(mismatch) => Err(VerifyError(mismatch.to_string()).into()),
+      None => Ok(report.timesteps),
+    }
+  }
+
+  /// Run the given [Scheduler] like [FileRunner::verify], but instead of stopping at the first
+  /// output that doesn't match, keep going until the end of the data (or until `max_steps`
+  /// timesteps, if given), and return a report of every mismatch.
+  ///
+  /// Errors in the same cases as [FileRunner::verify], except for mismatched outputs.
+  pub fn verify_report(
+    &mut self,
+    scheduler: &mut Scheduler,
+    inputs: HashMap<&str, InputBus<'_>>,
+    outputs: HashMap<&str, OutputBus<'_>>,
+    max_steps: Option<usize>,
+  ) -> Result<VerifyReport, Box<dyn Error>> {
+    self.run(scheduler, &inputs, &outputs, max_steps, 0, false)
+  }
+
+  fn run(
+    &mut self,
+    scheduler: &mut Scheduler,
+    inputs: &HashMap<&str, InputBus<'_>>,
+    outputs: &HashMap<&str, OutputBus<'_>>,
+    max_steps: Option<usize>,
+    tol: u32,
+    stop_on_mismatch: bool,
+  ) -> Result<VerifyReport, Box<dyn Error>> {
+    self.check_keys(inputs, outputs)?;
+    self.prime(inputs)?;
+
+    let mut report = VerifyReport {
+      timesteps: 0,
+      mismatches: vec![],
+      order_mismatches: vec![],
+      passed: true,
+    };
+
+    while max_steps.is_none_or(|max| report.timesteps < max) {
+      let split_line = match self.read_data_row()? {
+        Some(row) => row,
+        None => break,
+      };
+
+      self.set_inputs(&split_line, inputs)?;
+
+      for _ in 0..self.steps_per_row {
+        scheduler.advance()?;
+      }
+      report.timesteps += 1;
+
+      self.check_outputs(
+        &split_line,
+        report.timesteps,
+        outputs,
+        tol,
+        &mut report.mismatches,
+      )?;
+      self.check_order(&split_line, report.timesteps, &mut report.order_mismatches)?;
+      if stop_on_mismatch && !(report.mismatches.is_empty() && report.order_mismatches.is_empty()) {
+        break;
+      }
+    }
+
+    report.passed = report.mismatches.is_empty() && report.order_mismatches.is_empty();
+    Ok(report)
+  }
+
+  /// Check the outputs for one timestep against a row of the data, adding any that aren't within
+  /// `tol` of the expected values to `mismatches`.
+  fn check_outputs(
+    &self,
+    split_line: &[String],
+    timestep: usize,
+    outputs: &HashMap<&str, OutputBus<'_>>,
+    tol: u32,
+    mismatches: &mut Vec<Mismatch>,
+  ) -> Result<(), Box<dyn Error>> {
+    for (index, name, format) in self.outputs.iter() {
+      let value_from_file = split_line[*index].as_str();
+      let expected: Vec<i32> = if !value_from_file.is_empty() && value_from_file != WILDCARD {
+        value_from_file
+          .split_whitespace()
+          .map(parse_value)
+          .collect::<Result<_, _>>()?
+      } else {
+        vec![]
+      };
+
+      let actual = match outputs.get(name.as_str()) {
+        None => {
+          return error!("Expected output bus '{}', but not present", name);
+        }
+        Some(OutputBus::Simple(_)) if value_from_file == WILDCARD => continue,
+        Some(OutputBus::XBus(sink)) if value_from_file == WILDCARD => {
+          // Still drain the sink, so these values don't show up in the next timestep.
+          sink.queue_into(&mut Vec::new())?;
           continue;
         }
+        Some(OutputBus::Simple(atomic)) => {
+          if expected.is_empty() {
+            continue;
+          } else if expected.len() > 1 {
+            return error!(
+              "Multiple values expected for simple output '{}': {:?}",
+              name, expected
+            );
+          }
+          vec![atomic.load(Ordering::Relaxed)]
+        }
+        Some(OutputBus::XBus(sink)) => {
+          let mut actual = Vec::new();
+          sink.queue_into(&mut actual)?;
+          actual
+        }
+      };
 
-        let values: Vec<&str> = value_from_file.split(' ').collect();
+      let matches = expected.len() == actual.len()
+        && expected
+          .iter()
+          .zip(actual.iter())
+          .all(|(e, a)| (*e as i64 - *a as i64).abs() <= tol as i64);
+      if !matches {
+        mismatches.push(Mismatch {
+          timestep,
+          name: name.clone(),
+          expected,
+          actual,
+          format: *format,
+        });
+      }
+    }
 
-        match inputs.get(name.as_str()) {
-          None => {
-            return error!("Expected input bus '{}', but not present", name);
-          }
-          Some(InputBus::Simple(atomic)) => {
-            if values.is_empty() {
-              continue;
-            } else if values.len() > 1 {
-              return error!(
-                "Multiple values given for simple input '{}': {:?}",
-                name, values
-              );
-            }
-            atomic.store(values[0].parse()?, Ordering::Relaxed)
+    Ok(())
+  }
+
+  /// Check the writes logged during one timestep against the `order` field of a row of the data,
+  /// adding a mismatch if they differ.
+  fn check_order(
+    &self,
+    split_line: &[String],
+    timestep: usize,
+    mismatches: &mut Vec<OrderMismatch>,
+  ) -> Result<(), Box<dyn Error>> {
+    let (Some(index), Some(log)) = (self.order, self.write_log.as_ref()) else {
+      return Ok(());
+    };
+
+    let actual: Vec<(String, i32)> = log
+      .take()
+      .into_iter()
+      .map(|(name, value)| (String::from(name), value))
+      .collect();
+    let value_from_file = split_line[index].as_str();
+    if value_from_file == WILDCARD {
+      return Ok(());
+    }
+
+    let expected = value_from_file
+      .split_whitespace()
+      .map(parse_event)
+      .collect::<Result<Vec<_>, _>>()?;
+    if expected != actual {
+      mismatches.push(OrderMismatch {
+        timestep,
+        expected,
+        actual,
+      });
+    }
+    Ok(())
+  }
+
+  /// Check that every key in the given maps corresponds to a field in the data's header, so that a
+  /// typo can't make verification pass vacuously.
+  fn check_keys(
+    &self,
+    inputs: &HashMap<&str, InputBus<'_>>,
+    outputs: &HashMap<&str, OutputBus<'_>>,
+  ) -> Result<(), Box<dyn Error>> {
+    for key in inputs.keys() {
+      if !self.inputs.iter().any(|(_, name)| name == key) {
+        return error!("Input bus '{}' given, but not present in the data", key);
+      }
+    }
+    for key in outputs.keys() {
+      if !self.outputs.iter().any(|(_, name, _)| name == key) {
+        return error!("Output bus '{}' given, but not present in the data", key);
+      }
+    }
+    match (self.order, &self.write_log) {
+      (Some(_), None) => {
+        Err(VerifyError("The data has an order field, but no write log was given".into()).into())
+      }
+      (None, Some(_)) => {
+        Err(VerifyError("A write log was given, but the data has no order field".into()).into())
+      }
+      _ => Ok(()),
+    }
+  }
+
+  /// Set the inputs for one timestep from a row of the data.
+  fn set_inputs(
+    &self,
+    split_line: &[String],
+    inputs: &HashMap<&str, InputBus<'_>>,
+  ) -> Result<(), Box<dyn Error>> {
+    for (index, name) in self.inputs.iter() {
+      let value_from_file = split_line[*index].as_str();
+      if value_from_file.is_empty() {
+        continue;
+      }
+
+      let values: Vec<&str> = value_from_file.split_whitespace().collect();
+
+      match inputs.get(name.as_str()) {
+        None => {
+          return error!("Expected input bus '{}', but not present", name);
+        }
+        Some(InputBus::Simple(atomic)) => {
+          if values.is_empty() {
+            continue;
+          } else if values.len() > 1 {
+            return error!(
+              "Multiple values given for simple input '{}': {:?}",
+              name, values
+            );
           }
-          Some(InputBus::XBus(source)) => {
-            for v in values {
-              source.inject(v.parse()?)
-            }
+          atomic.store(parse_value(values[0])?, Ordering::Relaxed)
+        }
+        Some(InputBus::XBus(source)) => {
+          for v in values {
+            source.inject(parse_value(v)?)
           }
         }
       }
+    }
 
-      scheduler.advance();
-      timestep_number += 1;
+    Ok(())
+  }
 
-      for (index, name) in self.outputs.iter() {
-        let value_from_file = split_line[*index];
-        let expected: Vec<&str> = if !value_from_file.is_empty() {
-          value_from_file.split(' ').collect()
-        } else {
-          vec![]
-        };
+  /// Run the given [Scheduler] like [FileRunner::verify], but instead of checking the output
+  /// columns of the data, write a copy of the data to `out_stream` with the actual outputs filled
+  /// in. The result can be checked by hand and then used as the data for [FileRunner::verify].
+  ///
+  /// Simple outputs are recorded as their value at the end of each timestep, and XBus outputs as
+  /// the (possibly empty) list of values written during each timestep.
+  ///
+  /// Returns the number of timesteps recorded.
+  pub fn record(
+    &mut self,
+    scheduler: &mut Scheduler,
+    inputs: HashMap<&str, InputBus<'_>>,
+    outputs: HashMap<&str, OutputBus<'_>>,
+    out_stream: &mut dyn Write,
+  ) -> Result<usize, Box<dyn Error>> {
+    self.check_keys(&inputs, &outputs)?;
+
+    let column_count = self.inputs.len() + self.outputs.len() + self.order.iter().count();
+    let mut header = vec![String::new(); column_count];
+    if let Some(index) = self.order {
+      header[index] = String::from("order");
+    }
+    for (index, name) in self.inputs.iter() {
+      header[*index] = format!("in {}", name);
+    }
+    for (index, name, format) in self.outputs.iter() {
+      header[*index] = format!("out {}{}", name, format.header_suffix());
+    }
+    writeln!(out_stream, "{}", header.join(","))?;
 
-        match outputs.get(name.as_str()) {
+    if let Some(split_line) = self.prime(&inputs)? {
+      writeln!(out_stream, "{}", split_line.join(","))?;
+    }
+
+    let mut timestep_number = 0;
+
+    while let Some(mut split_line) = self.read_data_row()? {
+      self.set_inputs(&split_line, &inputs)?;
+
+      for _ in 0..self.steps_per_row {
+        scheduler.advance()?;
+      }
+      timestep_number += 1;
+
+      for (index, name, format) in self.outputs.iter() {
+        split_line[*index] = match outputs.get(name.as_str()) {
           None => {
             return error!("Expected output bus '{}', but not present", name);
           }
-          Some(OutputBus::Simple(atomic)) => {
-            if expected.is_empty() {
-              continue;
-            } else if expected.len() > 1 {
-              return error!(
-                "Multiple values expected for simple output '{}': {:?}",
-                name, expected
-              );
-            }
-
-            let actual = atomic.load(Ordering::Relaxed);
-            if expected[0].parse::<i32>()? != actual {
-              return error!(
-                "Incorrect output '{}' at time {}: expected {}, got {}",
-                name, timestep_number, expected[0], actual
-              );
-            }
-          }
+          Some(OutputBus::Simple(atomic)) => format.format(atomic.load(Ordering::Relaxed)),
           Some(OutputBus::XBus(sink)) => {
             let mut actual = Vec::new();
-            sink.queue_into(&mut actual);
-
-            if expected.len() != actual.len() {
-              return error!(
-                "Incorrect number of values output for '{}' at timestep {}: expected {}, got {}",
-                name,
-                timestep_number,
-                expected.len(),
-                actual.len()
-              );
-            }
-
-            for i in 0..expected.len() {
-              if expected[i].parse::<i32>()? != actual[i] {
-                return error!(
-                  "Incorrect output '{}' at time {}: expected {:?}, got {:?}",
-                  name, timestep_number, expected, actual
-                );
-              }
-            }
+            sink.queue_into(&mut actual)?;
+            format.format_all(&actual, " ")
           }
         };
       }
+
+      if let (Some(index), Some(log)) = (self.order, self.write_log.as_ref()) {
+        split_line[index] = log
+          .take()
+          .iter()
+          .map(|(name, value)| format!("{}:{}", name, value))
+          .collect::<Vec<String>>()
+          .join(" ");
+      }
+
+      writeln!(out_stream, "{}", split_line.join(","))?;
     }
 
     Ok(timestep_number)
@@ -14,4 +14,5 @@ pub mod components;
 pub mod controller;
 pub mod filerunner;
 pub mod scheduler;
+pub mod trace;
 pub mod xbus;
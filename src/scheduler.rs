@@ -1,12 +1,36 @@
 //! Logic to run controllers in threads and coordinate their execution.
+//!
+//! Each controller gets its own OS thread, because controller code can block (in `sleep` or an
+//! XBus operation) anywhere in its call stack, and stable Rust has no way to suspend a call stack
+//! other than parking its thread. Only one controller runs at a time, so the threads don't
+//! compete for CPU, but starting them has a cost. When running many inputs through the same
+//! design, create the scheduler once and call [Scheduler::reset] between runs instead of creating
+//! a new one, so the threads are reused.
+//!
+//! Despite the threads, a run is deterministic: controllers runnable at the same time are woken
+//! one at a time in an order that depends only on their names (or on the seed, with
+//! [WakeOrder::Shuffled]), and XBus readers and writers are served in the order they blocked. So
+//! rerunning a design with the same inputs repeats the run exactly, and there's no need to record
+//! scheduling decisions to reproduce a problem. What can differ between runs is anything a
+//! controller or component computes from outside the simulation, like the seed chosen by
+//! `inputsource::random_unseeded`, which is returned so that it can be reused.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 use std::time::Duration;
 
-use crate::controller::{current_name, send_to_scheduler, start, Controller};
+use crate::components::inputsource::seeded_draw;
+use crate::components::simple::LoggedPin;
+use crate::controller::{
+  self, current_name, send_to_scheduler, start, trace, use_power, Controller, ControllerContext,
+  ExecError,
+};
+use crate::trace::{BusId, TraceEvent, TraceOp, Tracer};
 use crate::xbus::XBus;
 
 pub(crate) enum SleepToken {
@@ -14,166 +38,1164 @@ pub(crate) enum SleepToken {
   XBusSleep(XBus),
   XBusRead(XBus),
   XBusWrite(XBus),
+  /// Runnable as soon as either of the two conditions holds.
+  Either(Box<SleepToken>, Box<SleepToken>),
+  /// The controller returned [ExecError::Fault] with this message. Never runnable.
+  Faulted(String),
 }
 
 impl Debug for SleepToken {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
       Self::Time(arg0) => f.debug_tuple("Time").field(arg0).finish(),
-      Self::XBusSleep(_) => f.debug_tuple("XBusSleep").finish(),
-      Self::XBusRead(_) => f.debug_tuple("XBusRead").finish(),
-      Self::XBusWrite(_) => f.debug_tuple("XBusWrite").finish(),
+      Self::XBusSleep(bus) => f
+        .debug_tuple("XBusSleep")
+        .field(&format_args!("{}", bus.id()))
+        .finish(),
+      Self::XBusRead(bus) => f
+        .debug_tuple("XBusRead")
+        .field(&format_args!("{}", bus.id()))
+        .finish(),
+      Self::XBusWrite(bus) => f
+        .debug_tuple("XBusWrite")
+        .field(&format_args!("{}", bus.id()))
+        .finish(),
+      Self::Either(a, b) => f.debug_tuple("Either").field(a).field(b).finish(),
+      Self::Faulted(message) => f.debug_tuple("Faulted").field(message).finish(),
     }
   }
 }
 
-fn is_blocking(token: &SleepToken) -> bool {
+fn blocked_on(token: &SleepToken) -> Option<(BlockedOn, &XBus)> {
   match token {
-    SleepToken::Time(_) | SleepToken::XBusSleep(_) => false,
-    SleepToken::XBusRead(_) | SleepToken::XBusWrite(_) => true,
+    SleepToken::Time(_) | SleepToken::XBusSleep(_) | SleepToken::Faulted(_) => None,
+    SleepToken::XBusRead(bus) => Some((BlockedOn::Read, bus)),
+    SleepToken::XBusWrite(bus) => Some((BlockedOn::Write, bus)),
+    // Only blocked if neither condition can be fulfilled by time passing.
+    SleepToken::Either(a, b) => blocked_on(b).and(blocked_on(a)),
   }
 }
 
-pub(crate) type SleepMessage = (&'static str, SleepToken, Sender<bool>);
+/// Whether a controller sleeping with the given token can be woken at the given time.
+fn can_run(token: &SleepToken, name: &'static str, time: u32) -> bool {
+  match token {
+    SleepToken::Time(t) => time >= *t,
+    SleepToken::XBusSleep(bus) => bus.can_read_as(name),
+    SleepToken::XBusRead(bus) => !bus.is_read_pending(name),
+    SleepToken::XBusWrite(bus) => !bus.is_write_pending(name),
+    SleepToken::Either(a, b) => can_run(a, name, time) || can_run(b, name, time),
+    SleepToken::Faulted(_) => false,
+  }
+}
+
+/// Drop the named controller's pending writes to any XBus that drops unread values at the end of
+/// the timestep.
+fn drop_unread_writes(token: &SleepToken, name: &'static str) {
+  match token {
+    SleepToken::XBusWrite(bus) => bus.drop_unread_write(name),
+    SleepToken::Either(a, b) => {
+      drop_unread_writes(a, name);
+      drop_unread_writes(b, name);
+    }
+    _ => {}
+  }
+}
+
+/// Timestep sleep tokens come in as "for N timesteps" -- we need to add the current timestep
+/// number to know when to wake up.
+fn to_absolute(token: SleepToken, now: u32) -> SleepToken {
+  match token {
+    SleepToken::Time(t) => SleepToken::Time(now + t),
+    SleepToken::Either(a, b) => SleepToken::Either(
+      Box::new(to_absolute(*a, now)),
+      Box::new(to_absolute(*b, now)),
+    ),
+    tok => tok,
+  }
+}
+
+/// What a controller is sleeping on, as reported by [Scheduler::step_one].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SleepState {
+  /// Sleeping until the given timestep, from `sleep`.
+  Time(u32),
+  /// Sleeping until the bus is readable, from `XBus::sleep`.
+  XBusSleep(BusId),
+  /// Blocked in `XBus::read`.
+  XBusRead(BusId),
+  /// Blocked in `XBus::write`.
+  XBusWrite(BusId),
+  /// Waiting for either of two conditions, from `XBus::read_timeout` or `sleep_until`.
+  Either(Box<SleepState>, Box<SleepState>),
+  /// Stopped after returning [ExecError::Fault] with the given message.
+  Faulted(String),
+}
+
+impl From<&SleepToken> for SleepState {
+  fn from(token: &SleepToken) -> Self {
+    match token {
+      SleepToken::Time(t) => Self::Time(*t),
+      SleepToken::XBusSleep(bus) => Self::XBusSleep(bus.id()),
+      SleepToken::XBusRead(bus) => Self::XBusRead(bus.id()),
+      SleepToken::XBusWrite(bus) => Self::XBusWrite(bus.id()),
+      SleepToken::Either(a, b) => Self::Either(
+        Box::new(Self::from(a.as_ref())),
+        Box::new(Self::from(b.as_ref())),
+      ),
+      SleepToken::Faulted(message) => Self::Faulted(message.clone()),
+    }
+  }
+}
+
+/// Returned by [Scheduler::step_one].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepResult {
+  /// The named controller was woken, ran, and went back to sleep as described.
+  Ran {
+    name: &'static str,
+    sleep: SleepState,
+  },
+  /// No controllers are runnable in the current timestep.
+  Settled,
+}
+
+/// What a controller is blocked on when a deadlock is detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockedOn {
+  /// Blocked in `XBus::read`, waiting for something to write a value.
+  Read,
+  /// Blocked in `XBus::write`, waiting for something to consume the value.
+  Write,
+}
+
+/// One controller that is blocked when a deadlock is detected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blocked {
+  pub name: &'static str,
+  pub on: BlockedOn,
+  /// The bus the controller is blocked on.
+  pub bus: BusId,
+  /// The other controllers that have used that bus so far, sorted. These are the ones that could
+  /// have unblocked this controller. Controllers only count as users once they've slept on, read,
+  /// or written to the bus, so a deadlock in the first timestep may show fewer than expected.
+  pub others: Vec<&'static str>,
+}
+
+/// Returned by [Scheduler::advance] when no controllers are runnable but some are blocked on an
+/// XBus read or write.
+#[derive(Debug)]
+pub struct DeadlockError {
+  /// The timestep in which the deadlock happened.
+  pub time: u32,
+  /// The blocked controllers, sorted by name.
+  pub blocked: Vec<Blocked>,
+  /// A cycle of blocked controllers, if there is one, where each is blocked on a bus whose other
+  /// users include the next, and the last is blocked on a bus used by the first. Without a cycle,
+  /// the deadlock is because controllers are waiting on buses that nobody else is using right now.
+  pub cycle: Option<Vec<&'static str>>,
+}
+
+impl DeadlockError {
+  fn new(time: u32, mut blocked: Vec<Blocked>) -> DeadlockError {
+    blocked.sort_by_key(|b| b.name);
+    let cycle = find_cycle(&blocked);
+    DeadlockError {
+      time,
+      blocked,
+      cycle,
+    }
+  }
+}
+
+/// Find a cycle in the graph where each blocked controller points to the other blocked
+/// controllers using its bus.
+fn find_cycle(blocked: &[Blocked]) -> Option<Vec<&'static str>> {
+  let edges: HashMap<&'static str, &Vec<&'static str>> =
+    blocked.iter().map(|b| (b.name, &b.others)).collect();
+
+  fn visit(
+    name: &'static str,
+    edges: &HashMap<&'static str, &Vec<&'static str>>,
+    path: &mut Vec<&'static str>,
+    done: &mut HashSet<&'static str>,
+  ) -> Option<Vec<&'static str>> {
+    if let Some(pos) = path.iter().position(|n| *n == name) {
+      return Some(path[pos..].to_vec());
+    }
+    if done.contains(name) {
+      return None;
+    }
+    let next = edges.get(name)?;
+    path.push(name);
+    for other in next.iter() {
+      if let Some(cycle) = visit(other, edges, path, done) {
+        return Some(cycle);
+      }
+    }
+    path.pop();
+    done.insert(name);
+    None
+  }
+
+  let mut done = HashSet::new();
+  blocked
+    .iter()
+    .find_map(|b| visit(b.name, &edges, &mut vec![], &mut done))
+}
+
+impl Error for DeadlockError {}
+
+impl std::fmt::Display for DeadlockError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "No modules are runnable but some are blocking at time {}:",
+      self.time
+    )?;
+    for b in self.blocked.iter() {
+      let action = match b.on {
+        BlockedOn::Read => "reading from",
+        BlockedOn::Write => "writing to",
+      };
+      let bus = match b.bus.name() {
+        Some(name) => format!("bus '{}'", name),
+        None => String::from("a bus"),
+      };
+      if b.others.is_empty() {
+        write!(
+          f,
+          "\n  {} is blocked {} {} no other module has used",
+          b.name, action, bus
+        )?;
+      } else {
+        write!(
+          f,
+          "\n  {} is blocked {} {} also used by {}",
+          b.name,
+          action,
+          bus,
+          b.others.join(", ")
+        )?;
+      }
+    }
+    if let Some(cycle) = self.cycle.as_ref() {
+      write!(f, "\n  cycle: {} -> {}", cycle.join(" -> "), cycle[0])?;
+    }
+    Ok(())
+  }
+}
+
+/// Returned by [Scheduler::advance] when controllers keep waking each other up within a single
+/// timestep without ever settling.
+#[derive(Debug)]
+pub struct LivelockError {
+  /// The timestep in which the livelock happened.
+  pub time: u32,
+  /// The number of wake rounds that ran before giving up.
+  pub rounds: u32,
+  /// The names of the controllers that were still runnable after the last round, sorted.
+  pub running: Vec<&'static str>,
+}
+
+impl Error for LivelockError {}
+
+impl std::fmt::Display for LivelockError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "Modules still runnable after {} wake rounds at time {}: {:?}",
+      self.rounds, self.time, self.running
+    )
+  }
+}
+
+/// Returned by [Scheduler::advance] when a controller it woke didn't go back to sleep within the
+/// execute timeout, most likely because it's stuck in a loop that never calls `sleep` or an XBus
+/// operation. Controllers sleeping until a later timestep don't count; only the controller that is
+/// actually running can be stuck.
+///
+/// The controller's thread is still running, so the scheduler can't be used any further. Drop it
+/// rather than calling [Scheduler::end], which would wait for the thread forever.
+#[derive(Debug)]
+pub struct StuckError {
+  /// The timestep in which the controller was woken.
+  pub time: u32,
+  pub name: &'static str,
+  /// The timeout that passed, as set by [Scheduler::set_execute_timeout].
+  pub timeout: Duration,
+}
+
+impl Error for StuckError {}
+
+impl std::fmt::Display for StuckError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "Module {} didn't go back to sleep within {:?} at time {}; is it stuck in a loop?",
+      self.name, self.timeout, self.time
+    )
+  }
+}
+
+/// Returned by [Scheduler::try_advance].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdvanceOutcome {
+  /// Controllers were woken the given number of times.
+  Ran(u32),
+  /// No controller was runnable, so nothing happened in the timestep.
+  Idle,
+}
+
+/// The ways [Scheduler::advance] and its relatives can fail.
+#[derive(Debug)]
+pub enum AdvanceError {
+  Deadlock(DeadlockError),
+  Livelock(LivelockError),
+  Stuck(StuckError),
+  /// [Scheduler::run_until] ran for the given number of steps without its predicate holding.
+  StepLimit(u32),
+}
+
+impl Error for AdvanceError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      Self::Deadlock(e) => Some(e),
+      Self::Livelock(e) => Some(e),
+      Self::Stuck(e) => Some(e),
+      Self::StepLimit(_) => None,
+    }
+  }
+}
+
+impl std::fmt::Display for AdvanceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Deadlock(e) => write!(f, "{}", e),
+      Self::Livelock(e) => write!(f, "{}", e),
+      Self::Stuck(e) => write!(f, "{}", e),
+      Self::StepLimit(steps) => write!(f, "Condition not reached after {} timesteps", steps),
+    }
+  }
+}
+
+impl From<DeadlockError> for AdvanceError {
+  fn from(e: DeadlockError) -> Self {
+    Self::Deadlock(e)
+  }
+}
+
+impl From<LivelockError> for AdvanceError {
+  fn from(e: LivelockError) -> Self {
+    Self::Livelock(e)
+  }
+}
+
+impl From<StuckError> for AdvanceError {
+  fn from(e: StuckError) -> Self {
+    Self::Stuck(e)
+  }
+}
+
+/// Returned by [Scheduler::add_controller] when a controller with the same name is already being
+/// scheduled.
+#[derive(Debug)]
+pub struct DuplicateNameError(pub &'static str);
+
+impl Error for DuplicateNameError {}
+
+impl std::fmt::Display for DuplicateNameError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Duplicate controller name: {}", self.0)
+  }
+}
+
+/// The order in which [Scheduler::advance] wakes the controllers that are runnable in a wake
+/// round. Controllers run one at a time, so this decides, for instance, which of two controllers
+/// woken together gets to read a value from a shared bus first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeOrder {
+  /// Sorted by controller name.
+  ByName,
+  /// Shuffled randomly in each round, like the game's nondeterminism, but reproducibly: the same
+  /// seed gives the same order every run, and different seeds give unrelated orders.
+  Shuffled(u64),
+}
+
+/// How many times a controller has gone to sleep in each way, as returned by
+/// [Scheduler::sleep_profile]. Comparing these across controllers shows which one is the
+/// bottleneck: e.g. one that mostly blocks on writes is waiting for a slow consumer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SleepProfile {
+  /// Calls to `sleep`, including the implicit sleep before the first timestep.
+  pub time: u64,
+  /// Calls to `XBus::sleep` that had to wait.
+  pub xbus_sleep: u64,
+  /// Calls to `XBus::read` that had to wait.
+  pub xbus_read: u64,
+  /// Calls to `XBus::write` that had to wait.
+  pub xbus_write: u64,
+  /// Calls to `XBus::read_timeout` or `sleep_until` that had to wait.
+  pub timeout: u64,
+}
+
+impl SleepProfile {
+  fn count(&mut self, token: &SleepToken) {
+    match token {
+      SleepToken::Time(_) => self.time += 1,
+      SleepToken::XBusSleep(_) => self.xbus_sleep += 1,
+      SleepToken::XBusRead(_) => self.xbus_read += 1,
+      SleepToken::XBusWrite(_) => self.xbus_write += 1,
+      SleepToken::Either(..) => self.timeout += 1,
+      SleepToken::Faulted(_) => {}
+    }
+  }
+}
+
+/// Statistics about a run, returned by [Scheduler::end].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunStats {
+  /// The number of timesteps run.
+  pub timesteps: u32,
+  /// The number of times each controller was woken up, keyed by name.
+  pub wakes: HashMap<&'static str, u64>,
+  /// The power used by each controller, as in [Scheduler::power_usage].
+  pub power: HashMap<&'static str, u64>,
+  /// The controllers that stopped with a fault, as in [Scheduler::faults].
+  pub faults: HashMap<&'static str, String>,
+}
+
+/// The default cap on wake rounds per timestep; see [Scheduler::set_max_wake_rounds].
+pub const DEFAULT_MAX_WAKE_ROUNDS: u32 = 10000;
+
+/// The default time a controller may run before going back to sleep; see
+/// [Scheduler::set_execute_timeout].
+pub const DEFAULT_EXECUTE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The default number of executions in a row a controller may complete without sleeping before
+/// it faults; see [Scheduler::set_max_executions_without_sleep]. Executions that only drain queued
+/// values don't sleep, so this allows a controller to drain a queue of up to a thousand values in
+/// one timestep, while a controller that never sleeps faults almost at once instead of spinning
+/// until the [DEFAULT_EXECUTE_TIMEOUT].
+pub const DEFAULT_MAX_EXECUTIONS_WITHOUT_SLEEP: u32 = 1000;
+
+/// The scheduler's reply to a sleeping controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Wake {
+  /// The controller's sleep condition is fulfilled; keep running.
+  Run,
+  /// Unwind back to the top of the controller and start over; see [Scheduler::reset].
+  Reset,
+  /// The scheduler is ending; unwind and exit the thread.
+  Terminate,
+}
+
+pub(crate) type SleepMessage = (&'static str, SleepToken, Sender<Wake>);
+
+/// Each controller's `acc` and `dat` as of the last time its `execute` returned, keyed by name.
+pub(crate) type SharedRegs = Arc<Mutex<HashMap<&'static str, (i32, i32)>>>;
+
+/// Every XBus the scheduler's controllers have used, for [Scheduler::reset].
+pub(crate) type SharedBuses = Arc<Mutex<Vec<XBus>>>;
 
 /// Coordinates controllers as they advance through time, starting their threads, waking them up
 /// as their sleep conditions get fulfilled, and shutting down their threads when done.
 pub struct Scheduler {
-  time: u32,
+  time: Arc<AtomicU32>,
   join_handles: Vec<JoinHandle<()>>,
+  sender: Sender<SleepMessage>,
   receiver: Receiver<SleepMessage>,
-  sleepers: HashMap<&'static str, (SleepToken, Sender<bool>)>,
+  sleepers: HashMap<&'static str, (SleepToken, Sender<Wake>)>,
+  max_wake_rounds: u32,
+  execute_timeout: Duration,
+  step_hook: Option<Box<dyn FnMut(u32)>>,
+  power: HashMap<&'static str, Arc<AtomicU64>>,
+  tracer: Arc<Tracer>,
+  regs: SharedRegs,
+  buses: SharedBuses,
+  /// Shared with controller threads; see [Scheduler::set_max_executions_without_sleep].
+  max_unslept: Arc<AtomicU32>,
+  /// Controllers still to be woken in the current wake round.
+  round: VecDeque<&'static str>,
+  /// The number of wake rounds started in the current timestep.
+  rounds: u32,
+  wake_order: WakeOrder,
+  /// The number of random draws made so far for [WakeOrder::Shuffled].
+  wake_draws: u64,
+  /// The number of times each controller has been woken.
+  wakes: HashMap<&'static str, u64>,
+  profiles: HashMap<&'static str, SleepProfile>,
+  /// Controllers that are never woken, from [Scheduler::set_enabled].
+  disabled: HashSet<&'static str>,
+  logged_pins: Vec<Arc<LoggedPin>>,
 }
 
 /// Go to sleep until the given number of timesteps has passed.
 /// This function is meant to be called from controller code. Errors should be propagated out of
 /// `Controller::execute`.
-#[allow(clippy::result_unit_err)]
-pub fn sleep(steps: u32) -> Result<(), ()> {
+pub fn sleep(steps: u32) -> Result<(), ExecError> {
+  use_power();
+  trace(TraceOp::Sleep { steps });
   Scheduler::sleep(SleepToken::Time(steps))?;
   Ok(())
 }
 
+/// Go to sleep until the given XBus is readable, like `XBus::sleep`, but no later than timestep
+/// `time`. Returns whether the bus is readable on waking. If the bus is already readable or `time`
+/// has already been reached, this returns immediately.
+/// This function is meant to be called from controller code. Errors should be propagated out of
+/// `Controller::execute`.
+pub fn sleep_until(bus: &XBus, time: u32) -> Result<bool, ExecError> {
+  use_power();
+  trace(TraceOp::XBusSleepUntil {
+    bus: bus.id(),
+    time,
+  });
+
+  let now = current_time();
+  if !bus.can_read() && now < time {
+    Scheduler::sleep(SleepToken::Either(
+      Box::new(SleepToken::XBusSleep(bus.clone())),
+      Box::new(SleepToken::Time(time - now)),
+    ))?;
+  }
+  Ok(bus.can_read())
+}
+
+/// Get the current timestep number, as returned by [Scheduler::time].
+/// This function is meant to be called from controller code, e.g. to model the game's clock chip.
+pub fn current_time() -> u32 {
+  controller::current_time()
+}
+
 impl Scheduler {
-  /// Sleep until the condition described by the SleepToken is true. The reply is a boolean
-  /// indicating whether the system is terminating; if so, this function returns an Err result to
-  /// be propagated up to the top level of the thread.
+  /// Sleep until the condition described by the SleepToken is true. The reply says whether to keep
+  /// running; if the system is terminating or resetting, this function returns an Err result to be
+  /// propagated up to the top level of the thread.
   ///
   /// This function runs on controller threads.
-  pub(crate) fn sleep(token: SleepToken) -> Result<(), ()> {
+  pub(crate) fn sleep(token: SleepToken) -> Result<(), ExecError> {
     let (wakeup_sender, wakeup_receiver) = channel();
     let name = current_name();
 
     send_to_scheduler((name, token, wakeup_sender));
 
-    let keep_going = wakeup_receiver.recv().unwrap();
-
-    if keep_going {
-      Ok(())
-    } else {
-      Err(())
+    match wakeup_receiver.recv().unwrap() {
+      Wake::Run => Ok(()),
+      Wake::Reset => {
+        controller::set_resetting();
+        Err(ExecError::Terminating)
+      }
+      Wake::Terminate => Err(ExecError::Terminating),
     }
   }
 
   /// Create a new scheduler of the given controllers. All the controller threads will be given a
   /// `Sender` to send sleep messages to the scheduler, and the threads will be started.
+  ///
+  /// Panics if two controllers have the same name, since names are used as unique keys both here
+  /// and in XBuses.
   pub fn new(controllers: Vec<Box<dyn Controller + Send>>) -> Scheduler {
     let controller_count = controllers.len();
-    let (sender, receiver) = channel();
-    let join_handles: Vec<JoinHandle<()>> = controllers
-      .into_iter()
-      .map(|ctrl| start(ctrl, sender.clone()))
-      .collect();
 
+    let mut names = HashSet::with_capacity(controller_count);
+    for ctrl in controllers.iter() {
+      if !names.insert(ctrl.name()) {
+        panic!("{}", DuplicateNameError(ctrl.name()));
+      }
+    }
+
+    let (sender, receiver) = channel();
     let mut scheduler = Scheduler {
-      time: 0,
+      time: Arc::new(AtomicU32::new(0)),
+      sender,
       receiver,
-      join_handles,
+      join_handles: Vec::with_capacity(controller_count),
       sleepers: HashMap::with_capacity(controller_count),
+      max_wake_rounds: DEFAULT_MAX_WAKE_ROUNDS,
+      execute_timeout: DEFAULT_EXECUTE_TIMEOUT,
+      step_hook: None,
+      power: HashMap::with_capacity(controller_count),
+      tracer: Arc::new(Tracer::default()),
+      regs: SharedRegs::default(),
+      buses: SharedBuses::default(),
+      max_unslept: Arc::new(AtomicU32::new(DEFAULT_MAX_EXECUTIONS_WITHOUT_SLEEP)),
+      round: VecDeque::new(),
+      rounds: 0,
+      wake_order: WakeOrder::ByName,
+      wake_draws: 0,
+      wakes: HashMap::with_capacity(controller_count),
+      profiles: HashMap::with_capacity(controller_count),
+      disabled: HashSet::new(),
+      logged_pins: vec![],
     };
+    for ctrl in controllers {
+      scheduler.spawn(ctrl);
+    }
 
     // Populate "sleepers" by waiting until all controllers have reached their initial sleep.
-    scheduler.await_sleepers(controller_count);
+    scheduler.expect_sleepers(controller_count);
     scheduler
   }
 
+  /// Create a new scheduler like [Scheduler::new], of `count` controllers made by calling
+  /// `factory` with each index from 0 to `count - 1`. This is for levels with a bank of identical
+  /// controllers; give each a distinct name, e.g. with `controller::intern_name`.
+  pub fn new_from(
+    count: usize,
+    factory: impl FnMut(usize) -> Box<dyn Controller + Send>,
+  ) -> Scheduler {
+    Self::new((0..count).map(factory).collect())
+  }
+
+  /// Start a new controller alongside the existing ones, keeping their state. Like those passed to
+  /// [Scheduler::new], its body will not execute until the next call to [Scheduler::advance].
+  ///
+  /// Controller names must remain unique; if the name collides with an existing controller, the
+  /// new one is not started and an error is returned. This must only be called between calls to
+  /// [Scheduler::advance].
+  pub fn add_controller(
+    &mut self,
+    ctrl: Box<dyn Controller + Send>,
+  ) -> Result<(), DuplicateNameError> {
+    let name = ctrl.name();
+    if self.sleepers.contains_key(name) {
+      return Err(DuplicateNameError(name));
+    }
+
+    self.spawn(ctrl);
+    self.expect_sleepers(1);
+    Ok(())
+  }
+
+  /// Start a controller's thread, sharing this scheduler's state with it.
+  fn spawn(&mut self, ctrl: Box<dyn Controller + Send>) {
+    let power = Arc::new(AtomicU64::new(0));
+    self.power.insert(ctrl.name(), Arc::clone(&power));
+    let context = ControllerContext {
+      sender: self.sender.clone(),
+      clock: Arc::clone(&self.time),
+      power,
+      tracer: Arc::clone(&self.tracer),
+      regs: Arc::clone(&self.regs),
+      buses: Arc::clone(&self.buses),
+      max_unslept: Arc::clone(&self.max_unslept),
+    };
+    self.join_handles.push(start(ctrl, context));
+  }
+
+  /// The current timestep number: the number of times [Scheduler::advance] has been called.
+  pub fn time(&self) -> u32 {
+    self.time.load(Ordering::Relaxed)
+  }
+
+  /// Set the maximum number of wake rounds [Scheduler::advance] will run within one timestep
+  /// before giving up with a [LivelockError]. A wake round is one pass that wakes up every
+  /// runnable controller and waits for all of them to go back to sleep.
+  pub fn set_max_wake_rounds(&mut self, rounds: u32) {
+    self.max_wake_rounds = rounds;
+  }
+
+  /// Set how long a woken controller may run, in real time, before going back to sleep. If it
+  /// takes longer, [Scheduler::advance] gives up with a [StuckError] naming it. The default is
+  /// [DEFAULT_EXECUTE_TIMEOUT]; raise it for controllers that do heavy computation between
+  /// sleeps.
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+  /// # use shenzhen_vm::scheduler::{AdvanceError, Scheduler};
+  /// # use std::time::Duration;
+  /// struct Spinner;
+  /// impl Controller for Spinner {
+  ///   fn name(&self) -> &'static str { "spinner" }
+  ///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+  ///     // Forgot to sleep.
+  ///     loop {
+  ///       std::thread::sleep(Duration::from_millis(10));
+  ///     }
+  ///   }
+  /// }
+  ///
+  /// let mut scheduler = Scheduler::new(vec![Box::new(Spinner)]);
+  /// scheduler.set_execute_timeout(Duration::from_millis(50));
+  /// match scheduler.advance() {
+  ///   Err(AdvanceError::Stuck(e)) => assert_eq!(e.name, "spinner"),
+  ///   other => panic!("{:?}", other),
+  /// }
+  /// ```
+  pub fn set_execute_timeout(&mut self, timeout: Duration) {
+    self.execute_timeout = timeout;
+  }
+
+  /// Set how many executions in a row a controller may complete without sleeping before it faults,
+  /// as described at [Controller::execute]. The default is
+  /// [DEFAULT_MAX_EXECUTIONS_WITHOUT_SLEEP]; raise it for controllers that drain longer queues in
+  /// one timestep. This applies to every controller, including ones already running.
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::{inputsource, outputsink};
+  /// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+  /// # use shenzhen_vm::scheduler::Scheduler;
+  /// # use shenzhen_vm::xbus::XBus;
+  /// // Drains its input without sleeping, then sleeps once the input is empty.
+  /// struct Drainer(XBus, XBus);
+  /// impl Controller for Drainer {
+  ///   fn name(&self) -> &'static str { "drainer" }
+  ///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+  ///     self.0.sleep()?;
+  ///     self.1.write(self.0.read()?)
+  ///   }
+  /// }
+  ///
+  /// let (_, input) = inputsource::from_iter(true, 0..2000);
+  /// let (sink, output) = outputsink::new("out", false);
+  /// let mut scheduler = Scheduler::new(vec![Box::new(Drainer(input, output))]);
+  /// scheduler.set_max_executions_without_sleep(5000);
+  /// scheduler.advance().unwrap();
+  /// assert!(scheduler.faults().is_empty());
+  /// assert_eq!(sink.peek_all().len(), 2000);
+  /// # scheduler.end();
+  /// ```
+  pub fn set_max_executions_without_sleep(&mut self, executions: u32) {
+    self.max_unslept.store(executions, Ordering::Relaxed);
+  }
+
+  /// Enable or disable the named controller, for debugging. A disabled controller is never woken,
+  /// so it stays frozen wherever it's sleeping, and it doesn't count towards a deadlock even if
+  /// it's blocked on an XBus. Enabling it again lets it wake up as usual, as soon as its sleep
+  /// condition holds. Controllers are enabled by default.
+  ///
+  /// Panics if there's no controller with that name.
+  pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+    let name = match self.sleepers.get_key_value(name) {
+      Some((name, _)) => *name,
+      None => panic!("No controller named '{}'", name),
+    };
+    if enabled {
+      self.disabled.remove(name);
+    } else {
+      self.disabled.insert(name);
+      self.round.retain(|n| *n != name);
+    }
+  }
+
+  /// Sample `pin` each time a controller goes back to sleep, recording its changes. See
+  /// [LoggedPin].
+  pub fn log_pin(&mut self, pin: Arc<LoggedPin>) {
+    self.logged_pins.push(pin);
+  }
+
+  /// Set the order in which controllers that become runnable at the same time are woken. The
+  /// default is [WakeOrder::ByName].
+  pub fn set_wake_order(&mut self, order: WakeOrder) {
+    self.wake_order = order;
+    self.wake_draws = 0;
+  }
+
+  /// Set a function to be called at the end of every successful [Scheduler::advance], once all
+  /// controllers have settled. It receives the number of the timestep that just completed. This is
+  /// useful for logging or visualizing the state of buses and components every timestep.
+  pub fn set_step_hook(&mut self, hook: Box<dyn FnMut(u32)>) {
+    self.step_hook = Some(hook);
+  }
+
   /// Wait until we've heard from `expected_count` controllers over the channel, storing their
-  /// sleep tokens and response senders.
-  fn await_sleepers(&mut self, expected_count: usize) {
+  /// sleep tokens and response senders. Returns false if the execute timeout passes while waiting
+  /// for any one of them.
+  fn await_sleepers(&mut self, expected_count: usize) -> bool {
     let mut receive_count = 0;
 
     while receive_count < expected_count {
       // Wait with a timeout to catch infinite loops in controllers.
-      let (name, token, wakeup) = self
-        .receiver
-        .recv_timeout(Duration::from_millis(500))
-        .unwrap();
-
-      // Timestep sleep tokens come in as "for N timestep" -- we need to add the current timestep
-      // number to know when to wake up.
-      let real_token = match token {
-        SleepToken::Time(t) => SleepToken::Time(self.time + t),
-        tok => tok,
+      let (name, token, wakeup) = match self.receiver.recv_timeout(self.execute_timeout) {
+        Ok(message) => message,
+        Err(_) => return false,
       };
 
-      self.sleepers.insert(name, (real_token, wakeup));
+      self.profiles.entry(name).or_default().count(&token);
+      self
+        .sleepers
+        .insert(name, (to_absolute(token, self.time()), wakeup));
       receive_count += 1;
     }
+    true
+  }
+
+  /// Like [Scheduler::await_sleepers], for controllers getting to their initial sleep, which they
+  /// do without running any of their own code. Panics, naming the controllers that never arrived,
+  /// if it times out.
+  fn expect_sleepers(&mut self, expected_count: usize) {
+    if !self.await_sleepers(expected_count) {
+      let mut missing: Vec<&'static str> = self
+        .power
+        .keys()
+        .filter(|name| !self.sleepers.contains_key(*name))
+        .copied()
+        .collect();
+      missing.sort();
+      panic!(
+        "Modules didn't reach their initial sleep within {:?}: {:?}",
+        self.execute_timeout, missing
+      );
+    }
   }
 
   /// Advance the current timestep number, then continuously wake up controller threads whose
   /// sleep conditions are fulfilled (right time reached, XBus now readable, etc.) until none of
   /// them are runnable. If any threads are blocking on an XBus read or write when all become
-  /// non-runnable, panic (this indicates a deadlock).
+  /// non-runnable, return a [DeadlockError] naming them. If controllers are still runnable after
+  /// the maximum number of wake rounds, return a [LivelockError] naming them. If a controller
+  /// doesn't go back to sleep within the execute timeout, return a [StuckError] naming it.
+  ///
+  /// Controllers that are runnable together are woken one at a time, in the order set by
+  /// [Scheduler::set_wake_order], each running until it sleeps again before the next is woken.
+  ///
+  /// This is equivalent to calling [Scheduler::start_timestep], then [Scheduler::step_one] until
+  /// it returns [StepResult::Settled], then [Scheduler::finish_timestep].
   ///
   /// When a controller is created with `Controller::start`, its body will not execute until this
   /// function is called for the first time.
   ///
   /// This function must be called on the main thread.
-  pub fn advance(&mut self) {
-    self.time += 1;
-
-    let mut run_count = 1;
-    while run_count > 0 {
-      run_count = 0;
-
-      for (name, (token, wakeup)) in self.sleepers.iter() {
-        let can_run = match token {
-          SleepToken::Time(t) => self.time >= *t,
-          SleepToken::XBusSleep(bus) => bus.can_read(),
-          SleepToken::XBusRead(bus) => !bus.is_read_pending(name),
-          SleepToken::XBusWrite(bus) => !bus.is_write_pending(name),
-        };
-
-        if can_run {
-          wakeup.send(true).unwrap();
-          run_count += 1;
+  pub fn advance(&mut self) -> Result<(), AdvanceError> {
+    self.start_timestep();
+    while let StepResult::Ran { .. } = self.step_one()? {}
+    self.finish_timestep()
+  }
+
+  /// Like [Scheduler::advance], but report whether any controller ran, e.g. to detect that a design
+  /// has gone quiet.
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+  /// # use shenzhen_vm::scheduler::{sleep, AdvanceOutcome, Scheduler};
+  /// struct Slow;
+  /// impl Controller for Slow {
+  ///   fn name(&self) -> &'static str { "slow" }
+  ///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> { sleep(3) }
+  /// }
+  ///
+  /// let mut scheduler = Scheduler::new(vec![Box::new(Slow)]);
+  /// assert_eq!(scheduler.try_advance().unwrap(), AdvanceOutcome::Ran(1));
+  /// assert_eq!(scheduler.try_advance().unwrap(), AdvanceOutcome::Idle);
+  /// # scheduler.end();
+  /// ```
+  pub fn try_advance(&mut self) -> Result<AdvanceOutcome, AdvanceError> {
+    self.start_timestep();
+    let mut count = 0;
+    while let StepResult::Ran { .. } = self.step_one()? {
+      count += 1;
+    }
+    self.finish_timestep()?;
+    Ok(match count {
+      0 => AdvanceOutcome::Idle,
+      _ => AdvanceOutcome::Ran(count),
+    })
+  }
+
+  /// For debugging: advance the current timestep number without waking any controllers. Follow
+  /// this with calls to [Scheduler::step_one] and then [Scheduler::finish_timestep].
+  pub fn start_timestep(&mut self) {
+    self.time.fetch_add(1, Ordering::Relaxed);
+    self.round.clear();
+    self.rounds = 0;
+  }
+
+  /// For debugging: wake exactly one runnable controller and wait for it to go back to sleep.
+  ///
+  /// Controllers are woken in rounds, as in [Scheduler::advance]: when a round starts, every
+  /// runnable controller is queued in the order set by [Scheduler::set_wake_order], and each call
+  /// to this wakes the next one in the queue. A controller sleeping on an XBus with `XBus::sleep`
+  /// stays queued even if another controller in the same round consumes the value it was waiting
+  /// for, as in the game. Returns
+  /// [StepResult::Settled] once a new round would have nothing to wake, or a [LivelockError] if a
+  /// new round would exceed the maximum number of wake rounds.
+  pub fn step_one(&mut self) -> Result<StepResult, AdvanceError> {
+    if self.round.is_empty() {
+      let time = self.time();
+      controller::set_clock(Arc::clone(&self.time));
+      let mut runnable: Vec<&'static str> = self
+        .sleepers
+        .iter()
+        .filter(|(name, (token, _))| !self.disabled.contains(*name) && can_run(token, name, time))
+        .map(|(name, _)| *name)
+        .collect();
+
+      if runnable.is_empty() {
+        return Ok(StepResult::Settled);
+      }
+      runnable.sort();
+
+      // Everything is asleep at this point, so it's safe for the caller to call end().
+      if self.rounds >= self.max_wake_rounds {
+        return Err(
+          LivelockError {
+            time,
+            rounds: self.rounds,
+            running: runnable,
+          }
+          .into(),
+        );
+      }
+
+      if let WakeOrder::Shuffled(seed) = self.wake_order {
+        // Fisher-Yates, drawing from a counter so the whole run is reproducible from the seed.
+        for i in (1..runnable.len()).rev() {
+          let draw = seeded_draw(seed, self.wake_draws);
+          self.wake_draws += 1;
+          runnable.swap(i, (draw % (i as u64 + 1)) as usize);
         }
       }
 
-      // Wait until we've heard from as many threads as we just woke up.
-      self.await_sleepers(run_count);
+      self.round.extend(runnable);
+      self.rounds += 1;
+    }
+
+    let name = self.round.pop_front().unwrap();
+    self.sleepers[name].1.send(Wake::Run).unwrap();
+    *self.wakes.entry(name).or_insert(0) += 1;
+    if !self.await_sleepers(1) {
+      return Err(
+        StuckError {
+          time: self.time(),
+          name,
+          timeout: self.execute_timeout,
+        }
+        .into(),
+      );
+    }
+    for pin in self.logged_pins.iter() {
+      pin.sample();
+    }
+
+    Ok(StepResult::Ran {
+      name,
+      sleep: SleepState::from(&self.sleepers[name].0),
+    })
+  }
+
+  /// For debugging: conclude the current timestep once [Scheduler::step_one] has returned
+  /// [StepResult::Settled], checking for deadlock and calling the step hook.
+  pub fn finish_timestep(&mut self) -> Result<(), AdvanceError> {
+    let time = self.time();
+
+    for (name, (token, _)) in self.sleepers.iter() {
+      drop_unread_writes(token, name);
     }
 
     // Before we can conclude the timestep, all controllers must be sleeping until a target time
     // ("slp") or sleeping on an XBus ("slx"); they can't be blocked trying to read or write a
     // value to an XBus. If some modules are blocked, there's a deadlock: fail the execution.
-    if self.sleepers.iter().any(|(_, v)| is_blocking(&v.0)) {
-      panic!(
-        "No modules are runnable but some are blocking: {:?}",
-        self.sleepers
-      );
+    // Writers whose values were just dropped aren't blocked; they'll wake in the next timestep.
+    let blocked: Vec<Blocked> = self
+      .sleepers
+      .iter()
+      .filter(|(name, (token, _))| !self.disabled.contains(*name) && !can_run(token, name, time))
+      .filter_map(|(name, (token, _))| {
+        blocked_on(token).map(|(on, bus)| Blocked {
+          name,
+          on,
+          bus: bus.id(),
+          others: bus.users().into_iter().filter(|n| n != name).collect(),
+        })
+      })
+      .collect();
+
+    if !blocked.is_empty() {
+      return Err(DeadlockError::new(time, blocked).into());
+    }
+
+    if let Some(hook) = self.step_hook.as_mut() {
+      hook(time);
+    }
+
+    Ok(())
+  }
+
+  /// Like [Scheduler::advance], but panics on deadlock or livelock instead of returning an error.
+  pub fn advance_or_panic(&mut self) {
+    if let Err(e) = self.advance() {
+      panic!("{}", e);
     }
   }
 
-  /// Tell all controller threads to terminate, and wait for them to exit.
-  pub fn end(self) {
+  /// Call [Scheduler::advance] `n` times, stopping at the first deadlock or livelock. Useful for
+  /// letting a circuit warm up before checking it with [Scheduler::run_until].
+  pub fn advance_n(&mut self, n: u32) -> Result<(), AdvanceError> {
+    for _ in 0..n {
+      self.advance()?;
+    }
+    Ok(())
+  }
+
+  /// Call [Scheduler::advance] repeatedly until `predicate` returns true, checking it after each
+  /// timestep. Returns the number of timesteps taken, or [AdvanceError::StepLimit] if the
+  /// predicate still doesn't hold after `max_steps` timesteps.
+  pub fn run_until<F: FnMut() -> bool>(
+    &mut self,
+    max_steps: u32,
+    mut predicate: F,
+  ) -> Result<u32, AdvanceError> {
+    for step in 1..=max_steps {
+      self.advance()?;
+      if predicate() {
+        return Ok(step);
+      }
+    }
+    Err(AdvanceError::StepLimit(max_steps))
+  }
+
+  /// What each controller is currently sleeping on, sorted by name, e.g. for a status display.
+  /// Between calls to [Scheduler::advance], every controller is sleeping.
+  pub fn controller_states(&self) -> Vec<(&'static str, SleepState)> {
+    let mut states: Vec<(&'static str, SleepState)> = self
+      .sleepers
+      .iter()
+      .map(|(name, (token, _))| (*name, SleepState::from(token)))
+      .collect();
+    states.sort_by_key(|(name, _)| *name);
+    states
+  }
+
+  /// The power used by each controller so far, keyed by name. Since controllers are freeform Rust,
+  /// this is only a proxy for the game's power usage: each call to `sleep`, `XBus::sleep`,
+  /// `XBus::read`, or `XBus::write` costs one unit.
+  pub fn power_usage(&self) -> HashMap<&'static str, u64> {
+    self
+      .power
+      .iter()
+      .map(|(name, counter)| (*name, counter.load(Ordering::Relaxed)))
+      .collect()
+  }
+
+  /// How many times each controller has gone to sleep in each way, keyed by name. Operations that
+  /// complete without waiting, like a read from a bus with a value already available, aren't
+  /// counted.
+  pub fn sleep_profile(&self) -> HashMap<&'static str, SleepProfile> {
+    self.profiles.clone()
+  }
+
+  /// The message of each controller that has stopped by returning [ExecError::Fault], keyed by
+  /// name. Other controllers keep running after one faults.
+  pub fn faults(&self) -> HashMap<&'static str, String> {
+    self
+      .sleepers
+      .iter()
+      .filter_map(|(name, (token, _))| match token {
+        SleepToken::Faulted(message) => Some((*name, message.clone())),
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// The `acc` and `dat` registers of each controller, keyed by name. Registers live on the
+  /// controller threads, so each controller publishes them whenever its `execute` returns; values
+  /// changed partway through an `execute` that is still sleeping are not visible yet. Controllers
+  /// that haven't finished an `execute` yet show zeros. With the `serde` feature, the map can be
+  /// serialized as is, and deserialized into a `HashMap<String, (i32, i32)>`.
+  pub fn snapshot_regs(&self) -> HashMap<&'static str, (i32, i32)> {
+    self.regs.lock().unwrap().clone()
+  }
+
+  /// Turn recording of controllers' operations on or off. While on, every call to `sleep`,
+  /// `XBus::sleep`, `XBus::read`, and `XBus::write` is recorded with the controller's name and the
+  /// timestep, to be retrieved with [Scheduler::take_trace]. Tracing is off by default.
+  ///
+  /// This must only be called between calls to [Scheduler::advance].
+  pub fn set_tracing(&mut self, enabled: bool) {
+    self.tracer.set_enabled(enabled);
+  }
+
+  /// Remove and return all the events recorded so far, in the order they happened.
+  pub fn take_trace(&mut self) -> Vec<TraceEvent> {
+    self.tracer.take()
+  }
+
+  /// Restart the simulation from a clean state without restarting threads: every controller
+  /// unwinds out of `execute` back to its initial registers, as if newly started, and the timestep
+  /// number, power usage, and wake counts go back to zero. As with a new scheduler, controllers
+  /// don't run (or rerun `init`) until the next [Scheduler::advance].
+  ///
+  /// Every XBus a controller has used is cleared of values in flight: values controllers were
+  /// blocked trying to read or write, a value held by a coalescing bus, and a multicast value.
+  /// Input sources that count timesteps, like `inputsource::blocking_with_default`, start counting
+  /// again from zero.
+  ///
+  /// Other state held outside the controllers is not reset: memory contents, values queued in
+  /// input sources, output sinks, and FIFOs, and XBus stats. Like [Scheduler::end], this relies on
+  /// controllers propagating errors out of `execute`. This must only be called between calls to
+  /// [Scheduler::advance].
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+  /// # use shenzhen_vm::scheduler::{sleep, Scheduler};
+  /// # use shenzhen_vm::xbus::XBus;
+  /// struct Writer(XBus);
+  /// impl Controller for Writer {
+  ///   fn name(&self) -> &'static str { "writer" }
+  ///   fn execute(&self, reg: &mut Regs) -> Result<(), ExecError> {
+  ///     reg.acc += 1;
+  ///     self.0.write(reg.acc)?;
+  ///     sleep(1)
+  ///   }
+  /// }
+  ///
+  /// let bus = XBus::new_coalescing();
+  /// let mut scheduler = Scheduler::new(vec![Box::new(Writer(bus.clone()))]);
+  /// scheduler.advance_n(2).unwrap();
+  /// assert!(format!("{:?}", bus).contains("coalesced: Some(2)"));
+  ///
+  /// scheduler.reset();
+  /// assert_eq!(scheduler.time(), 0);
+  /// assert!(format!("{:?}", bus).contains("coalesced: None"));
+  /// # scheduler.end();
+  /// ```
+  pub fn reset(&mut self) {
+    let buses = self.buses.lock().unwrap().clone();
+    for bus in buses.iter() {
+      bus.reset();
+    }
+
+    self.time.store(0, Ordering::Relaxed);
+    self.round.clear();
+    self.rounds = 0;
+    self.wake_draws = 0;
+    self.wakes.clear();
+    self.profiles.clear();
+    for counter in self.power.values() {
+      counter.store(0, Ordering::Relaxed);
+    }
+
+    let count = self.sleepers.len();
+    for (_name, (_, wakeup)) in self.sleepers.drain() {
+      wakeup.send(Wake::Reset).unwrap();
+    }
+
+    // Wait for everyone to get back to their initial sleep.
+    self.expect_sleepers(count);
+  }
+
+  /// Tell all controller threads to terminate, and wait for them to exit. Returns statistics
+  /// about the whole run, for comparing designs.
+  pub fn end(self) -> RunStats {
+    let stats = RunStats {
+      timesteps: self.time(),
+      wakes: self
+        .sleepers
+        .keys()
+        .map(|name| (*name, self.wakes.get(name).copied().unwrap_or(0)))
+        .collect(),
+      power: self.power_usage(),
+      faults: self.faults(),
+    };
+
     for (_name, (_, wakeup)) in self.sleepers.iter() {
-      wakeup.send(false).unwrap();
+      wakeup.send(Wake::Terminate).unwrap();
     }
 
     for jh in self.join_handles.into_iter() {
       jh.join().unwrap();
     }
+
+    stats
   }
 }
@@ -0,0 +1,78 @@
+//! Opt-in recording of the operations controllers perform, for debugging timing issues between
+//! controllers. Turn it on with [crate::scheduler::Scheduler::set_tracing] and retrieve the events
+//! with [crate::scheduler::Scheduler::take_trace].
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Identifies an XBus in trace events. Clones of an XBus share the same ID. It's displayed as the
+/// bus's name if it was created with `XBus::new_named`, or as a number otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BusId(pub(crate) usize, pub(crate) Option<&'static str>);
+
+impl BusId {
+  /// The name the bus was created with, if any.
+  pub fn name(&self) -> Option<&'static str> {
+    self.1
+  }
+}
+
+impl std::fmt::Display for BusId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.1 {
+      Some(name) => f.write_str(name),
+      None => write!(f, "{:#x}", self.0),
+    }
+  }
+}
+
+/// An operation recorded by the tracer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceOp {
+  /// A call to `scheduler::sleep`.
+  Sleep { steps: u32 },
+  /// A call to `XBus::sleep`.
+  XBusSleep { bus: BusId },
+  /// A call to `scheduler::sleep_until`.
+  XBusSleepUntil { bus: BusId, time: u32 },
+  /// A call to `XBus::read` or `XBus::read_timeout`. This is recorded when the read starts, so it
+  /// may be followed by other controllers' events before the value arrives.
+  XBusRead { bus: BusId },
+  /// A call to `XBus::write`, recorded when the write starts.
+  XBusWrite { bus: BusId, value: i32 },
+}
+
+/// One traced operation, tagged with the controller that performed it and the timestep it
+/// happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent {
+  pub time: u32,
+  pub controller: &'static str,
+  pub op: TraceOp,
+}
+
+/// The trace buffer shared between the scheduler and its controllers. When tracing is disabled,
+/// recording costs a single atomic load.
+#[derive(Default)]
+pub(crate) struct Tracer {
+  enabled: AtomicBool,
+  events: Mutex<Vec<TraceEvent>>,
+}
+
+impl Tracer {
+  pub(crate) fn set_enabled(&self, enabled: bool) {
+    self.enabled.store(enabled, Ordering::Relaxed);
+  }
+
+  pub(crate) fn is_enabled(&self) -> bool {
+    self.enabled.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn record(&self, event: TraceEvent) {
+    self.events.lock().unwrap().push(event);
+  }
+
+  pub(crate) fn take(&self) -> Vec<TraceEvent> {
+    std::mem::take(&mut *self.events.lock().unwrap())
+  }
+}
@@ -1,21 +1,54 @@
 //! Logic to model reading from and writing to an XBus.
 
-use std::collections::HashMap;
+use std::collections::BTreeSet;
+use std::fmt::Debug;
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::{Arc, Mutex};
 
-use crate::controller::current_name;
+use crate::controller::{
+  current_name, current_time, register_bus, take_fault, trace, use_power, ExecError,
+};
 use crate::scheduler::{Scheduler, SleepToken};
+use crate::trace::{BusId, TraceOp};
 
 pub(crate) trait TSource {
   fn can_read(&self) -> bool;
   fn read(&self) -> i32;
+
+  /// Whether this source holds values written to the same bus, like a FIFO. If so, its values are
+  /// older than any blocked writer's, so readers take them first.
+  fn holds_writes(&self) -> bool {
+    false
+  }
+
+  /// Called when a scheduler whose controllers use this bus is reset, setting the timestep number
+  /// back to zero, so that a source that keeps track of time can start over.
+  fn reset(&self) {}
 }
 
 pub(crate) trait TSink {
+  /// Whether the sink can currently accept a value. If not, writers block as if there were no
+  /// sink connected.
+  fn can_write(&self) -> bool {
+    true
+  }
+
   fn write(&self, _: i32);
 }
 
+/// Counts of the values that have passed over an XBus, for finding bottlenecks; see
+/// [XBus::stats].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XBusStats {
+  /// Reads that got a value, whether from a writer or a connected source.
+  pub reads: u64,
+  /// Writes whose value was consumed, whether by a reader or a connected sink.
+  pub writes: u64,
+  /// The most writers ever blocked on the bus at once. If this is high, producers are outrunning
+  /// consumers.
+  pub max_pending_writers: usize,
+}
+
 /// Represents XBus connections between components, and the logic of reading, writing, and sleeping
 /// on them.
 ///
@@ -29,18 +62,234 @@ struct Inner {
   sources: Vec<Arc<dyn TSource + Send + Sync>>,
   sinks: Vec<Arc<dyn TSink + Send + Sync>>,
 
-  pending_readers: HashMap<&'static str, Arc<AtomicI32>>,
-  pending_writers: HashMap<&'static str, i32>,
+  // Pending readers and writers are kept in the order they blocked, and served in that order.
+  pending_readers: Vec<(&'static str, Arc<AtomicI32>)>,
+  pending_writers: Vec<(&'static str, i32)>,
+
+  /// The names of all controllers that have ever slept on, read from, or written to this bus.
+  users: BTreeSet<&'static str>,
+
+  /// Whether a write goes to every pending reader instead of just the first.
+  multicast: bool,
+
+  /// On a multicast bus, the value most recently read, the timestep it was read in, and the
+  /// controllers that have read it. It stays readable by everyone else until the timestep ends.
+  broadcast: Option<(u32, i32, BTreeSet<&'static str>)>,
+
+  /// Whether values still pending at the end of a timestep are dropped, from
+  /// [XBus::new_game_accurate].
+  drop_unread: bool,
+
+  /// Writers whose values were dropped, and which haven't woken up to find out yet.
+  dropped: Vec<&'static str>,
+
+  /// Whether a write that nobody takes right away replaces any unread value instead of blocking,
+  /// from [XBus::new_coalescing].
+  coalescing: bool,
+
+  /// On a coalescing bus, the most recent unread value.
+  coalesced: Option<i32>,
+
+  /// A name for diagnostics, from [XBus::new_named].
+  name: Option<&'static str>,
+
+  stats: XBusStats,
+}
+
+impl Inner {
+  /// The multicast value from the current timestep, if the named controller hasn't read it yet.
+  fn broadcast_for(&self, controller_name: &'static str) -> Option<i32> {
+    match &self.broadcast {
+      Some((time, value, readers))
+        if *time == current_time() && !readers.contains(controller_name) =>
+      {
+        Some(*value)
+      }
+      _ => None,
+    }
+  }
+
+  /// Give the oldest blocked writer's value to a sink that can now accept it, if there is one, so
+  /// that the writer can wake up.
+  fn admit_pending_write(&mut self) {
+    if self.pending_writers.is_empty() {
+      return;
+    }
+    if let Some(sink) = self.sinks.iter().find(|sink| sink.can_write()) {
+      let (_, value) = self.pending_writers.remove(0);
+      sink.write(value);
+      self.stats.writes += 1;
+    }
+  }
+}
+
+impl Debug for XBus {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let inner = self.inner.lock().unwrap();
+    let readers: Vec<&str> = inner.pending_readers.iter().map(|(n, _)| *n).collect();
+    f.debug_struct("XBus")
+      .field("name", &inner.name)
+      .field("pending_readers", &readers)
+      .field("pending_writers", &inner.pending_writers)
+      .field("coalesced", &inner.coalesced)
+      .finish()
+  }
+}
+
+impl Default for XBus {
+  fn default() -> Self {
+    Self::new()
+  }
 }
 
 impl XBus {
   /// Create a new XBus.
   pub fn new() -> XBus {
+    Self::make(false, false, false, None)
+  }
+
+  /// Create a new XBus with a name, which is shown in its `Debug` output, in deadlock errors, and
+  /// in trace events (through [BusId]), to tell it apart from other buses when debugging. The name
+  /// has no effect on the bus's behavior and needn't be unique.
+  ///
+  /// ```
+  /// # use shenzhen_vm::xbus::XBus;
+  /// let bus = XBus::new_named("radio");
+  /// assert_eq!(bus.id().name(), Some("radio"));
+  /// assert_eq!(bus.id().to_string(), "radio");
+  /// assert_eq!(XBus::new().id().name(), None);
+  /// ```
+  pub fn new_named(name: &'static str) -> XBus {
+    Self::make(false, false, false, Some(name))
+  }
+
+  /// Create a new XBus on which a written value can be read by every controller, rather than only
+  /// the first. A write is delivered to every controller blocked reading from the bus, and the
+  /// value then stays readable, once per controller, until the end of the timestep. So
+  /// controllers that wait with `XBus::sleep` and then read all get the same value, whether they
+  /// run before or after the writer. This departs from the game, where only one reader gets each
+  /// value.
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+  /// # use shenzhen_vm::scheduler::{sleep, Scheduler};
+  /// # use shenzhen_vm::xbus::XBus;
+  /// # use std::sync::atomic::{AtomicI32, Ordering};
+  /// # use std::sync::Arc;
+  /// struct Reader(&'static str, XBus, Arc<AtomicI32>);
+  /// impl Controller for Reader {
+  ///   fn name(&self) -> &'static str { self.0 }
+  ///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+  ///     self.1.sleep()?;
+  ///     self.2.store(self.1.read()?, Ordering::Relaxed);
+  ///     sleep(1)
+  ///   }
+  /// }
+  ///
+  /// // Runs before the readers, since controllers are woken in name order.
+  /// struct Writer(XBus);
+  /// impl Controller for Writer {
+  ///   fn name(&self) -> &'static str { "a-writer" }
+  ///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+  ///     self.0.write(42)?;
+  ///     sleep(1)
+  ///   }
+  /// }
+  ///
+  /// let bus = XBus::new_multicast_read();
+  /// let results: Vec<Arc<AtomicI32>> = (0..3).map(|_| Arc::new(AtomicI32::new(0))).collect();
+  /// let mut scheduler = Scheduler::new(vec![
+  ///   Box::new(Reader("reader0", bus.clone(), results[0].clone())),
+  ///   Box::new(Reader("reader1", bus.clone(), results[1].clone())),
+  ///   Box::new(Reader("reader2", bus.clone(), results[2].clone())),
+  ///   Box::new(Writer(bus)),
+  /// ]);
+  /// scheduler.advance_n(2).unwrap();
+  /// scheduler.end();
+  /// assert!(results.iter().all(|r| r.load(Ordering::Relaxed) == 42));
+  /// ```
+  pub fn new_multicast_read() -> XBus {
+    Self::make(true, false, false, None)
+  }
+
+  /// Create a new XBus on which written values don't outlive the timestep they're written in. If a
+  /// controller is still blocked writing when the scheduler finishes a timestep, its value is
+  /// dropped, and instead of causing a deadlock, it wakes up in the next timestep with its write
+  /// done. [XBus::try_write] tells it whether the value was dropped.
+  ///
+  /// This is for modeling transient connections, and for catching consumers that never run. On an
+  /// ordinary XBus, as in the game, a write blocks until something reads it.
+  ///
+  /// ```
+  /// # use shenzhen_vm::controller::{Controller, ExecError, Regs};
+  /// # use shenzhen_vm::scheduler::{sleep, Scheduler};
+  /// # use shenzhen_vm::xbus::XBus;
+  /// # use std::sync::atomic::{AtomicI32, Ordering};
+  /// # use std::sync::Arc;
+  /// struct Writer(XBus, Arc<AtomicI32>);
+  /// impl Controller for Writer {
+  ///   fn name(&self) -> &'static str { "writer" }
+  ///   fn execute(&self, _: &mut Regs) -> Result<(), ExecError> {
+  ///     if !self.0.try_write(1)? {
+  ///       self.1.fetch_add(1, Ordering::Relaxed);
+  ///     }
+  ///     sleep(1)
+  ///   }
+  /// }
+  ///
+  /// let dropped = Arc::new(AtomicI32::new(0));
+  /// let bus = XBus::new_game_accurate();
+  /// let mut scheduler = Scheduler::new(vec![Box::new(Writer(bus, dropped.clone()))]);
+  /// scheduler.advance_n(4).unwrap();
+  /// # scheduler.end();
+  /// // The writer wrote in timesteps 1 and 3, and found out each value was dropped in the next.
+  /// assert_eq!(dropped.load(Ordering::Relaxed), 2);
+  /// ```
+  pub fn new_game_accurate() -> XBus {
+    Self::make(false, true, false, None)
+  }
+
+  /// Create a new XBus on which writes never block. If no reader or sink takes a value right away,
+  /// it's held until something reads it, replacing any value that was already held, so a reader
+  /// only ever sees the latest one. Reads block as usual until a value is available.
+  ///
+  /// This is for modeling something like a display, where a slow consumer should skip stale values
+  /// rather than hold up the producer. Unlike a [latch](crate::components::latch), each value is
+  /// read at most once. The game has no such bus.
+  ///
+  /// ```
+  /// # use shenzhen_vm::xbus::XBus;
+  /// let bus = XBus::new_coalescing();
+  /// for value in [1, 2, 3] {
+  ///   bus.write(value).unwrap();
+  /// }
+  /// assert_eq!(bus.read(), Ok(3));
+  /// assert_eq!((bus.stats().reads, bus.stats().writes), (1, 1));
+  /// ```
+  pub fn new_coalescing() -> XBus {
+    Self::make(false, false, true, None)
+  }
+
+  fn make(
+    multicast: bool,
+    drop_unread: bool,
+    coalescing: bool,
+    name: Option<&'static str>,
+  ) -> XBus {
     let inner = Mutex::new(Inner {
       sources: vec![],
       sinks: vec![],
-      pending_readers: HashMap::new(),
-      pending_writers: HashMap::new(),
+      pending_readers: vec![],
+      pending_writers: vec![],
+      users: BTreeSet::new(),
+      multicast,
+      broadcast: None,
+      drop_unread,
+      dropped: vec![],
+      coalescing,
+      coalesced: None,
+      name,
+      stats: XBusStats::default(),
     });
     XBus {
       inner: Arc::new(inner),
@@ -55,8 +304,11 @@ impl XBus {
   /// NB: even after returning from this, immediately reading from the same XBus may block!
   /// This behavior is the same as in the game: every controller `slx`-ing on a bus will wake up
   /// when something writes a value onto the bus, even though only one will get to read that value.
-  #[allow(clippy::result_unit_err)]
-  pub fn sleep(&self) -> Result<(), ()> {
+  pub fn sleep(&self) -> Result<(), ExecError> {
+    use_power();
+    trace(TraceOp::XBusSleep { bus: self.id() });
+    self.add_user(&mut self.inner.lock().unwrap());
+
     if !self.can_read() {
       Scheduler::sleep(SleepToken::XBusSleep(self.clone()))?;
     }
@@ -64,69 +316,282 @@ impl XBus {
   }
 
   /// For controller code: read from the bus, blocking until a value is available.
-  #[allow(clippy::result_unit_err)]
-  pub fn read(&self) -> Result<i32, ()> {
+  ///
+  /// If several controllers are blocked writing to the bus, the one that blocked first is read
+  /// from. Otherwise, the first connected source with a value available is read from.
+  pub fn read(&self) -> Result<i32, ExecError> {
+    use_power();
+    trace(TraceOp::XBusRead { bus: self.id() });
+
     // The eventual writer will put its value in here.
-    let cell: Arc<AtomicI32>;
+    let cell = match self.take_or_wait() {
+      Ok(value) => return take_fault().map(|_| value),
+      Err(cell) => cell,
+    };
+
+    Scheduler::sleep(SleepToken::XBusRead(self.clone()))?;
+    Ok(cell.load(Ordering::Relaxed))
+  }
+
+  /// For controller code: like [XBus::read], but give up if no value arrives within `steps`
+  /// timesteps, returning `Ok(None)`. With `steps` of 0, this returns a value only if one is
+  /// available in the current timestep.
+  ///
+  /// Unlike a blocked `read`, a controller waiting here doesn't count towards a deadlock.
+  pub fn read_timeout(&self, steps: u32) -> Result<Option<i32>, ExecError> {
+    use_power();
+    trace(TraceOp::XBusRead { bus: self.id() });
+
+    let cell = match self.take_or_wait() {
+      Ok(value) => return take_fault().map(|_| Some(value)),
+      Err(cell) => cell,
+    };
 
+    Scheduler::sleep(SleepToken::Either(
+      Box::new(SleepToken::XBusRead(self.clone())),
+      Box::new(SleepToken::Time(steps)),
+    ))?;
+
+    // If we're still queued, we timed out; dequeue ourselves under the lock so that no writer can
+    // hand us a value afterwards.
+    let mut xbus = self.inner.lock().unwrap();
+    let name = current_name();
+    match xbus.pending_readers.iter().position(|(n, _)| *n == name) {
+      Some(index) => {
+        xbus.pending_readers.remove(index);
+        Ok(None)
+      }
+      None => Ok(Some(cell.load(Ordering::Relaxed))),
+    }
+  }
+
+  /// Take a value from a pending writer or a source if there is one. Otherwise, put the current
+  /// controller into the pending readers queue and return the cell that a writer will put its value
+  /// in.
+  fn take_or_wait(&self) -> Result<i32, Arc<AtomicI32>> {
+    let mut xbus = self.inner.lock().unwrap();
+    self.add_user(&mut xbus);
+
+    // A source holding earlier writes goes ahead of the writers blocked behind it. Reading frees
+    // up room in it, so the oldest blocked write can then move in.
+    if let Some(source) = xbus
+      .sources
+      .iter()
+      .find(|source| source.holds_writes() && source.can_read())
     {
-      let mut xbus = self.inner.lock().unwrap();
+      let value = source.read();
+      xbus.stats.reads += 1;
+      xbus.admit_pending_write();
+      return Ok(value);
+    }
 
-      // If there's a pending write from another component, just take it.
-      if !xbus.pending_writers.is_empty() {
-        let key = *xbus.pending_writers.iter().next().unwrap().0;
-        let value = xbus.pending_writers.remove(key).unwrap();
-        return Ok(value);
+    // If there's a pending write from another component, just take it.
+    if !xbus.pending_writers.is_empty() {
+      let (_, value) = xbus.pending_writers.remove(0);
+      xbus.stats.reads += 1;
+      xbus.stats.writes += 1;
+      if xbus.multicast {
+        xbus.broadcast = Some((current_time(), value, BTreeSet::from([current_name()])));
       }
+      return Ok(value);
+    }
 
-      // TODO: pick a source randomly
-      for source in xbus.sources.iter() {
-        if source.can_read() {
-          return Ok(source.read());
-        }
+    if let Some(value) = xbus.broadcast_for(current_name()) {
+      xbus.stats.reads += 1;
+      if let Some((_, _, readers)) = xbus.broadcast.as_mut() {
+        readers.insert(current_name());
       }
+      return Ok(value);
+    }
 
-      // Put ourselves into the pending readers queue.
-      let name = current_name();
-      cell = Arc::new(AtomicI32::new(0));
-      xbus.pending_readers.insert(name, cell.clone());
-    } // Unlock the mutex before sleeping.
+    if let Some(value) = xbus.coalesced.take() {
+      xbus.stats.reads += 1;
+      xbus.stats.writes += 1;
+      return Ok(value);
+    }
 
-    Scheduler::sleep(SleepToken::XBusRead(self.clone()))?;
-    Ok(cell.load(Ordering::Relaxed))
+    // TODO: pick a source randomly
+    if let Some(source) = xbus.sources.iter().find(|source| source.can_read()) {
+      let value = source.read();
+      xbus.stats.reads += 1;
+      return Ok(value);
+    }
+
+    // Put ourselves into the pending readers queue.
+    let name = current_name();
+    let cell = Arc::new(AtomicI32::new(0));
+    xbus.pending_readers.push((name, cell.clone()));
+    Err(cell)
   }
 
   /// For controller code: write to the bus, blocking until something else consumes it.
-  #[allow(clippy::result_unit_err)]
-  pub fn write(&self, val: i32) -> Result<(), ()> {
+  ///
+  /// If several controllers are blocked reading from the bus, the one that blocked first gets the
+  /// value. Otherwise, the first connected sink that can accept the value gets it.
+  pub fn write(&self, val: i32) -> Result<(), ExecError> {
+    self.try_write(val).map(|_| ())
+  }
+
+  /// For controller code: like [XBus::write], but return whether the value was consumed. This can
+  /// only be false on a bus created with [XBus::new_game_accurate], when nothing read the value in
+  /// the timestep it was written. On a bus created with [XBus::new_coalescing], a value that's held
+  /// for a later reader counts as consumed.
+  pub fn try_write(&self, val: i32) -> Result<bool, ExecError> {
+    use_power();
+    trace(TraceOp::XBusWrite {
+      bus: self.id(),
+      value: val,
+    });
+
     {
       let mut xbus = self.inner.lock().unwrap();
+      self.add_user(&mut xbus);
 
-      // If there's a reader already waiting, give it our value.
+      // If there's a reader already waiting, give it our value. On a multicast bus, give it to all
+      // of them.
+      if xbus.multicast && !xbus.pending_readers.is_empty() {
+        xbus.stats.reads += xbus.pending_readers.len() as u64;
+        xbus.stats.writes += 1;
+        let mut readers = BTreeSet::new();
+        for (name, cell) in xbus.pending_readers.drain(..) {
+          cell.store(val, Ordering::Relaxed);
+          readers.insert(name);
+        }
+        xbus.broadcast = Some((current_time(), val, readers));
+        return Ok(true);
+      }
       if !xbus.pending_readers.is_empty() {
-        let key = *xbus.pending_readers.iter().next().unwrap().0;
-        let cell = xbus.pending_readers.remove(key).unwrap();
+        let (_, cell) = xbus.pending_readers.remove(0);
         cell.store(val, Ordering::Relaxed);
-        return Ok(());
+        xbus.stats.reads += 1;
+        xbus.stats.writes += 1;
+        return Ok(true);
       }
 
       // TODO: pick a sink randomly
-      if !xbus.sinks.is_empty() {
-        xbus.sinks[0].write(val);
-        return Ok(());
+      if let Some(sink) = xbus.sinks.iter().find(|sink| sink.can_write()) {
+        sink.write(val);
+        xbus.stats.writes += 1;
+        return take_fault().map(|_| true);
+      }
+
+      if xbus.coalescing {
+        xbus.coalesced = Some(val);
+        return Ok(true);
       }
 
       // Put our value into the pending writers queue.
       let name = current_name();
-      xbus.pending_writers.insert(name, val);
+      xbus.pending_writers.push((name, val));
+      xbus.stats.max_pending_writers = xbus
+        .stats
+        .max_pending_writers
+        .max(xbus.pending_writers.len());
     } // Unlock the mutex before sleeping.
 
     Scheduler::sleep(SleepToken::XBusWrite(self.clone()))?;
-    Ok(())
+
+    let mut xbus = self.inner.lock().unwrap();
+    let name = current_name();
+    match xbus.dropped.iter().position(|n| *n == name) {
+      Some(index) => {
+        xbus.dropped.remove(index);
+        Ok(false)
+      }
+      None => Ok(true),
+    }
+  }
+
+  /// Disconnect a component that was connected to this bus as a source, such as an
+  /// [InputSource](crate::components::inputsource::InputSource), matching by pointer identity.
+  /// Returns whether it was connected. Values it already provided are unaffected.
+  ///
+  /// Disconnecting while a controller is blocked on the bus has undefined results; only do this
+  /// between calls to `Scheduler::advance`.
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::inputsource;
+  /// let (source, bus) = inputsource::from_iter(true, [1, 2]);
+  /// assert!(bus.disconnect_source(&source));
+  /// assert!(!bus.disconnect_source(&source));
+  ///
+  /// let (other, _) = inputsource::from_iter(true, [3]);
+  /// other.attach(&bus);
+  /// assert_eq!(bus.read(), Ok(3));
+  /// ```
+  pub fn disconnect_source<T: ?Sized>(&self, source: &Arc<T>) -> bool {
+    let target = Arc::as_ptr(source) as *const ();
+    let sources = &mut self.inner.lock().unwrap().sources;
+    let before = sources.len();
+    sources.retain(|s| Arc::as_ptr(s) as *const () != target);
+    sources.len() != before
+  }
+
+  /// Disconnect a component that was connected to this bus as a sink, such as an
+  /// [OutputSink](crate::components::outputsink::OutputSink), matching by pointer identity.
+  /// Returns whether it was connected. As with [XBus::disconnect_source], only do this between
+  /// calls to `Scheduler::advance`.
+  pub fn disconnect_sink<T: ?Sized>(&self, sink: &Arc<T>) -> bool {
+    let target = Arc::as_ptr(sink) as *const ();
+    let sinks = &mut self.inner.lock().unwrap().sinks;
+    let before = sinks.len();
+    sinks.retain(|s| Arc::as_ptr(s) as *const () != target);
+    sinks.len() != before
+  }
+
+  /// Counts of the values that have passed over this bus since it was created, shared by all its
+  /// clones.
+  ///
+  /// ```
+  /// # use shenzhen_vm::components::{inputsource, outputsink};
+  /// let (_, bus) = inputsource::from_iter(true, [1, 2]);
+  /// bus.read().unwrap();
+  /// bus.read().unwrap();
+  /// assert_eq!((bus.stats().reads, bus.stats().writes), (2, 0));
+  ///
+  /// let (_, bus) = outputsink::new("out", false);
+  /// bus.write(7).unwrap();
+  /// assert_eq!((bus.stats().reads, bus.stats().writes), (0, 1));
+  /// ```
+  pub fn stats(&self) -> XBusStats {
+    self.inner.lock().unwrap().stats
+  }
+
+  /// An identifier for this bus, shared by all its clones. Trace events use it to tell buses apart.
+  pub fn id(&self) -> BusId {
+    let name = self.inner.lock().unwrap().name;
+    BusId(Arc::as_ptr(&self.inner) as *const () as usize, name)
   }
 
   // Everything below here is crate-internal only.
 
+  /// Record the current controller as a user of this bus, and the first time it uses the bus, tell
+  /// its scheduler about the bus.
+  fn add_user(&self, inner: &mut Inner) {
+    if inner.users.insert(current_name()) {
+      register_bus(self);
+    }
+  }
+
+  pub(crate) fn same_bus(&self, other: &XBus) -> bool {
+    Arc::ptr_eq(&self.inner, &other.inner)
+  }
+
+  /// Drop everything in flight on this bus, for [Scheduler::reset]: pending reads and writes, and
+  /// any held coalesced or multicast value. Connected sources that keep track of time are reset
+  /// too.
+  pub(crate) fn reset(&self) {
+    let mut inner = self.inner.lock().unwrap();
+    inner.pending_readers.clear();
+    inner.pending_writers.clear();
+    inner.dropped.clear();
+    inner.coalesced = None;
+    inner.broadcast = None;
+    for source in inner.sources.iter() {
+      source.reset();
+    }
+  }
+
   pub(crate) fn connect_source(&self, source: Arc<dyn TSource + Send + Sync>) {
     self.inner.lock().unwrap().sources.push(source);
   }
@@ -135,9 +600,18 @@ impl XBus {
     self.inner.lock().unwrap().sinks.push(sink);
   }
 
+  /// Whether the current controller could read a value without blocking.
   pub(crate) fn can_read(&self) -> bool {
+    self.can_read_as(current_name())
+  }
+
+  /// Whether the named controller could read a value without blocking.
+  pub(crate) fn can_read_as(&self, controller_name: &'static str) -> bool {
     let inner = self.inner.lock().unwrap();
-    !inner.pending_writers.is_empty() || inner.sources.iter().any(|src| src.can_read())
+    !inner.pending_writers.is_empty()
+      || inner.coalesced.is_some()
+      || inner.broadcast_for(controller_name).is_some()
+      || inner.sources.iter().any(|src| src.can_read())
   }
 
   pub(crate) fn is_read_pending(&self, controller_name: &'static str) -> bool {
@@ -146,7 +620,8 @@ impl XBus {
       .lock()
       .unwrap()
       .pending_readers
-      .contains_key(controller_name)
+      .iter()
+      .any(|(name, _)| *name == controller_name)
   }
 
   pub(crate) fn is_write_pending(&self, controller_name: &'static str) -> bool {
@@ -155,6 +630,28 @@ impl XBus {
       .lock()
       .unwrap()
       .pending_writers
-      .contains_key(controller_name)
+      .iter()
+      .any(|(name, _)| *name == controller_name)
+  }
+
+  /// If this bus drops unread values, drop the named controller's pending write, if any, so that
+  /// it can wake up.
+  pub(crate) fn drop_unread_write(&self, controller_name: &'static str) {
+    let mut inner = self.inner.lock().unwrap();
+    if !inner.drop_unread {
+      return;
+    }
+    let before = inner.pending_writers.len();
+    inner
+      .pending_writers
+      .retain(|(name, _)| *name != controller_name);
+    if inner.pending_writers.len() != before {
+      inner.dropped.push(controller_name);
+    }
+  }
+
+  /// The names of all controllers that have used this bus, sorted.
+  pub(crate) fn users(&self) -> Vec<&'static str> {
+    self.inner.lock().unwrap().users.iter().copied().collect()
   }
 }